@@ -24,7 +24,7 @@ fn main() -> ExResult<()> {
     println!("Dropped CAP_SYS_NICE.");
     let has_sys_nice = caps::has_cap(None, CapSet::Effective, Capability::CAP_SYS_NICE);
     assert!(has_sys_nice.is_ok());
-    assert_eq!(has_sys_nice.unwrap_or(true), false);
+    assert!(!has_sys_nice.unwrap_or(true));
     let r = renice(-20);
     assert_eq!(r, -1);
     println!("Unprivileged, unable to raise priority to -20.");
@@ -53,15 +53,17 @@ fn main() -> ExResult<()> {
 #[cfg(target_env = "musl")]
 const PRIO_PROCESS: i32 = libc::PRIO_PROCESS;
 #[cfg(not(target_env = "musl"))]
+#[allow(clippy::unnecessary_cast)]
 const PRIO_PROCESS: u32 = libc::PRIO_PROCESS as u32;
 
-fn renice(prio: libc::c_int) -> libc::c_int {
+fn renice(prio: i32) -> i32 {
     // This is not proper logic, as it does not record errno value on error.
     unsafe { libc::setpriority(PRIO_PROCESS, 0, prio) }
 }
 
-fn proc_nice() -> libc::c_int {
+fn proc_nice() -> i32 {
     // This is not proper logic, as it does not special-case -1 nor record errno.
+    #[allow(clippy::unnecessary_cast)]
     let r = unsafe { libc::getpriority(PRIO_PROCESS as u32, 0) };
     if r == -1 {
         panic!("getpriority failed.");
@@ -1,89 +1,82 @@
 //! Implementation of Ambient set.
 
-use crate::errors::CapsError;
-use crate::nr;
+use crate::errors::{CapsError, ErrorKind};
+use crate::raw;
 use crate::runtime;
-use crate::{Capability, CapsHashSet};
-use std::io::Error;
+use crate::{CapSet, Capability, CapsBitSet, CapsHashSet};
 
 pub fn clear() -> Result<(), CapsError> {
-    let ret = unsafe { libc::prctl(nr::PR_CAP_AMBIENT, nr::PR_CAP_AMBIENT_CLEAR_ALL, 0, 0, 0) };
-    match ret {
-        0 => Ok(()),
-        _ => Err(format!(
-            "PR_CAP_AMBIENT_CLEAR_ALL failure: {}",
-            Error::last_os_error()
-        )
-        .into()),
-    }
+    raw::ambient_clear().map_err(|e| {
+        CapsError::from_errno("PR_CAP_AMBIENT_CLEAR_ALL", e)
+            .on_einval(ErrorKind::KernelTooOld)
+            .with_set(CapSet::Ambient)
+    })
 }
 
 pub fn drop(cap: Capability) -> Result<(), CapsError> {
-    let ret = unsafe {
-        libc::prctl(
-            nr::PR_CAP_AMBIENT,
-            nr::PR_CAP_AMBIENT_LOWER,
-            libc::c_uint::from(cap.index()),
-            0,
-            0,
-        )
-    };
-    match ret {
-        0 => Ok(()),
-        _ => Err(format!("PR_CAP_AMBIENT_LOWER failure: {}", Error::last_os_error()).into()),
-    }
+    drop_index(cap.index()).map_err(|e| e.with_cap(cap))
+}
+
+/// Lower a capability by index, even if unknown to this library.
+pub fn drop_index(index: u8) -> Result<(), CapsError> {
+    raw::ambient_lower(index).map_err(|e| index_error("PR_CAP_AMBIENT_LOWER", index, e))
 }
 
 pub fn has_cap(cap: Capability) -> Result<bool, CapsError> {
-    let ret = unsafe {
-        libc::prctl(
-            nr::PR_CAP_AMBIENT,
-            nr::PR_CAP_AMBIENT_IS_SET,
-            libc::c_uint::from(cap.index()),
-            0,
-            0,
-        )
-    };
-    match ret {
-        0 => Ok(false),
-        1 => Ok(true),
-        _ => Err(format!("PR_CAP_AMBIENT_IS_SET failure: {}", Error::last_os_error()).into()),
-    }
+    has_index(cap.index()).map_err(|e| e.with_cap(cap))
+}
+
+fn has_index(index: u8) -> Result<bool, CapsError> {
+    raw::ambient_has(index).map_err(|e| index_error("PR_CAP_AMBIENT_IS_SET", index, e))
 }
 
 pub fn raise(cap: Capability) -> Result<(), CapsError> {
-    let ret = unsafe {
-        libc::prctl(
-            nr::PR_CAP_AMBIENT,
-            nr::PR_CAP_AMBIENT_RAISE,
-            libc::c_uint::from(cap.index()),
-            0,
-            0,
-        )
-    };
-    match ret {
-        0 => Ok(()),
-        _ => Err(format!("PR_CAP_AMBIENT_RAISE failure: {}", Error::last_os_error()).into()),
-    }
+    raise_index(cap.index()).map_err(|e| e.with_cap(cap))
+}
+
+fn raise_index(index: u8) -> Result<(), CapsError> {
+    raw::ambient_raise(index).map_err(|e| index_error("PR_CAP_AMBIENT_RAISE", index, e))
 }
 
 pub fn read() -> Result<CapsHashSet, CapsError> {
-    let mut res = super::CapsHashSet::new();
-    for c in runtime::thread_all_supported() {
-        if has_cap(c)? {
-            res.insert(c);
-        }
-    }
-    Ok(res)
+    read_bits().map(CapsHashSet::from)
+}
+
+/// Read the Ambient set, including capabilities unknown to this library.
+pub fn read_bits() -> Result<CapsBitSet, CapsError> {
+    runtime::ambient_set_supported()?;
+    let last_cap = runtime::last_cap()?;
+    let mask = raw::ambient_read(last_cap)
+        .map_err(|e| index_error("PR_CAP_AMBIENT_IS_SET", last_cap, e))?;
+    Ok(CapsBitSet::from_raw(mask))
 }
 
-pub fn set(value: &super::CapsHashSet) -> Result<(), CapsError> {
-    for c in runtime::thread_all_supported() {
-        if value.contains(&c) {
-            raise(c)?;
+pub fn set(value: &CapsHashSet) -> Result<(), CapsError> {
+    set_bits(CapsBitSet::from(value))
+}
+
+pub fn set_bits(value: CapsBitSet) -> Result<(), CapsError> {
+    runtime::ambient_set_supported()?;
+    for index in 0..=runtime::last_cap()? {
+        if value.contains_index(index) {
+            raise_index(index)?;
         } else {
-            drop(c)?;
+            drop_index(index)?;
         };
     }
     Ok(())
 }
+
+/// Build the error for a failed per-capability ambient operation.
+///
+/// `EINVAL` means either that the capability is unknown to the kernel,
+/// or that the kernel does not support ambient capabilities at all.
+fn index_error(op: &str, index: u8, errno: i32) -> CapsError {
+    let err = CapsError::from_errno(op, errno);
+    let kind = match runtime::last_cap() {
+        _ if !runtime::has_ambient() => ErrorKind::KernelTooOld,
+        Ok(last_cap) if index > last_cap => ErrorKind::InvalidCapability,
+        _ => ErrorKind::InvalidInput,
+    };
+    err.on_einval(kind).with_set(CapSet::Ambient)
+}
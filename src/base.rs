@@ -1,161 +1,119 @@
-use crate::errors::CapsError;
+use crate::errors::{CapsError, ErrorKind};
 use crate::nr;
-use crate::{CapSet, Capability, CapsHashSet};
-use std::io::Error;
+use crate::raw;
+use crate::runtime;
+use crate::{bounding, BaseState, CapSet, Capability, CapsBitSet, CapsHashSet};
 
-#[allow(clippy::unreadable_literal)]
-const CAPS_V3: u32 = 0x20080522;
-
-fn capget(hdr: &mut CapUserHeader, data: &mut CapUserData) -> Result<(), CapsError> {
-    let r = unsafe { libc::syscall(nr::CAPGET, hdr, data) };
-    match r {
-        0 => Ok(()),
-        _ => Err(format!("capget failure: {}", Error::last_os_error()).into()),
-    }
-}
-
-fn capset(hdr: &mut CapUserHeader, data: &CapUserData) -> Result<(), CapsError> {
-    let r = unsafe { libc::syscall(nr::CAPSET, hdr, data) };
-    match r {
-        0 => Ok(()),
-        _ => Err(format!("capset failure: {}", Error::last_os_error()).into()),
+/// Return the `capget(2)` ABI version to use, among the ones `raw` supports.
+pub(crate) fn version() -> u32 {
+    match runtime::capget_version() {
+        v @ nr::LINUX_CAPABILITY_VERSION_1
+        | v @ nr::LINUX_CAPABILITY_VERSION_2
+        | v @ nr::LINUX_CAPABILITY_VERSION_3 => v,
+        _ => nr::LINUX_CAPABILITY_VERSION_3,
     }
 }
 
 pub fn has_cap(tid: i32, cset: CapSet, cap: Capability) -> Result<bool, CapsError> {
-    let mut hdr = CapUserHeader {
-        version: CAPS_V3,
-        pid: tid,
-    };
-    let mut data: CapUserData = Default::default();
-    capget(&mut hdr, &mut data)?;
-    let caps: u64 = match cset {
-        CapSet::Effective => (u64::from(data.effective_s1) << 32) + u64::from(data.effective_s0),
-        CapSet::Inheritable => {
-            (u64::from(data.inheritable_s1) << 32) + u64::from(data.inheritable_s0)
-        }
-        CapSet::Permitted => (u64::from(data.permitted_s1) << 32) + u64::from(data.permitted_s0),
-        CapSet::Bounding | CapSet::Ambient => return Err("not a base set".into()),
-    };
-    let has_cap = (caps & cap.bitmask()) != 0;
-    Ok(has_cap)
+    let caps = read_bits(tid, cset)?;
+    Ok(caps.contains(cap))
 }
 
 pub fn clear(tid: i32, cset: CapSet) -> Result<(), CapsError> {
-    let mut hdr = CapUserHeader {
-        version: CAPS_V3,
-        pid: tid,
-    };
-    let mut data: CapUserData = Default::default();
-    capget(&mut hdr, &mut data)?;
+    let mut state = read_all(tid)?;
     match cset {
-        CapSet::Effective => {
-            data.effective_s0 = 0;
-            data.effective_s1 = 0;
-        }
-        CapSet::Inheritable => {
-            data.inheritable_s0 = 0;
-            data.inheritable_s1 = 0;
-        }
+        CapSet::Effective => state.effective.clear(),
+        CapSet::Inheritable => state.inheritable.clear(),
         CapSet::Permitted => {
-            data.effective_s0 = 0;
-            data.effective_s1 = 0;
-            data.permitted_s0 = 0;
-            data.permitted_s1 = 0;
+            state.effective.clear();
+            state.permitted.clear();
+        }
+        CapSet::Bounding | CapSet::Ambient => {
+            return Err(CapsError::new(ErrorKind::InvalidInput, "not a base set").with_set(cset))
         }
-        CapSet::Bounding | CapSet::Ambient => return Err("not a base set".into()),
     }
-    capset(&mut hdr, &data)
+    set_all(tid, &state)
 }
 
 pub fn read(tid: i32, cset: CapSet) -> Result<CapsHashSet, CapsError> {
-    let mut hdr = CapUserHeader {
-        version: CAPS_V3,
-        pid: tid,
-    };
-    let mut data: CapUserData = Default::default();
-    capget(&mut hdr, &mut data)?;
-    let caps: u64 = match cset {
-        CapSet::Effective => (u64::from(data.effective_s1) << 32) + u64::from(data.effective_s0),
-        CapSet::Inheritable => {
-            (u64::from(data.inheritable_s1) << 32) + u64::from(data.inheritable_s0)
-        }
-        CapSet::Permitted => (u64::from(data.permitted_s1) << 32) + u64::from(data.permitted_s0),
-        CapSet::Bounding | CapSet::Ambient => return Err("not a base set".into()),
-    };
-    let mut res = CapsHashSet::new();
-    for c in super::all() {
-        if (caps & c.bitmask()) != 0 {
-            res.insert(c);
-        }
-    }
-    Ok(res)
+    read_bits(tid, cset).map(CapsHashSet::from)
+}
+
+pub fn read_bits(tid: i32, cset: CapSet) -> Result<CapsBitSet, CapsError> {
+    read_all(tid)?.get(cset)
+}
+
+/// Read Effective, Permitted and Inheritable sets with a single `capget`.
+pub fn read_all(tid: i32) -> Result<BaseState, CapsError> {
+    let masks = raw::capget(version(), tid)
+        .map_err(|e| CapsError::from_errno("capget", e).with_tid(tid))?;
+    Ok(BaseState::from(&masks))
 }
 
 pub fn set(tid: i32, cset: CapSet, value: &CapsHashSet) -> Result<(), CapsError> {
-    let mut hdr = CapUserHeader {
-        version: CAPS_V3,
-        pid: tid,
+    set_bits(tid, cset, CapsBitSet::from(value))
+}
+
+pub fn set_bits(tid: i32, cset: CapSet, value: CapsBitSet) -> Result<(), CapsError> {
+    let mut state = read_all(tid)?;
+    state.set(cset, value)?;
+    set_all(tid, &state)
+}
+
+/// Set Effective, Permitted and Inheritable sets with a single `capset`.
+pub fn set_all(tid: i32, value: &BaseState) -> Result<(), CapsError> {
+    let err = match raw::capset(version(), tid, &raw::BaseMasks::from(value)) {
+        Ok(()) => return Ok(()),
+        Err(e) => CapsError::from_errno("capset", e).with_tid(tid),
     };
-    let mut data: CapUserData = Default::default();
-    capget(&mut hdr, &mut data)?;
-    {
-        let (s1, s0) = match cset {
-            CapSet::Effective => (&mut data.effective_s1, &mut data.effective_s0),
-            CapSet::Inheritable => (&mut data.inheritable_s1, &mut data.inheritable_s0),
-            CapSet::Permitted => (&mut data.permitted_s1, &mut data.permitted_s0),
-            CapSet::Bounding | CapSet::Ambient => return Err("not a base set".into()),
-        };
-        *s1 = 0;
-        *s0 = 0;
-        for c in value {
-            match c.index() {
-                0..=31 => {
-                    *s0 |= c.bitmask() as u32;
-                }
-                32..=63 => {
-                    *s1 |= (c.bitmask() >> 32) as u32;
-                }
-                _ => return Err(format!("overlarge capability index {}", c.index()).into()),
+    // On rejection, try to pinpoint which `capset(2)` rule was violated.
+    // This is best effort: on failure, the original error is kept.
+    if tid == 0 && err.errno() == Some(libc::EPERM) {
+        if let (Ok(current), Ok(bset)) = (read_all(tid), bounding::read_bits()) {
+            let setpcap = current.effective.contains(Capability::CAP_SETPCAP);
+            if let Err(e) = value.validate_transition(&current, bset, setpcap) {
+                let mut err = err;
+                err.message = format!("{}: {}", err.message, e.message);
+                err.cap = e.cap;
+                return Err(err);
             }
         }
     }
-    capset(&mut hdr, &data)?;
-    Ok(())
+    Err(err)
 }
 
 pub fn drop(tid: i32, cset: CapSet, cap: Capability) -> Result<(), CapsError> {
-    let mut caps = read(tid, cset)?;
-    if caps.remove(&cap) {
-        set(tid, cset, &caps)?;
+    let mut state = read_all(tid)?;
+    if state.drop(cset, cap)? {
+        set_all(tid, &state)?;
     };
     Ok(())
 }
 
 pub fn raise(tid: i32, cset: CapSet, cap: Capability) -> Result<(), CapsError> {
-    let mut caps = read(tid, cset)?;
-    if caps.insert(cap) {
-        set(tid, cset, &caps)?;
+    let mut state = read_all(tid)?;
+    if state.raise(cset, cap)? {
+        set_all(tid, &state)?;
     };
     Ok(())
 }
 
-#[derive(Debug)]
-#[repr(C)]
-struct CapUserHeader {
-    // Linux capabilities version (runtime kernel support)
-    version: u32,
-    // Process ID (thread)
-    pid: i32,
+impl From<&raw::BaseMasks> for BaseState {
+    fn from(masks: &raw::BaseMasks) -> Self {
+        Self {
+            effective: CapsBitSet::from_raw(masks.effective),
+            permitted: CapsBitSet::from_raw(masks.permitted),
+            inheritable: CapsBitSet::from_raw(masks.inheritable),
+        }
+    }
 }
 
-#[derive(Debug, Default, Clone)]
-#[repr(C)]
-struct CapUserData {
-    effective_s0: u32,
-    permitted_s0: u32,
-    inheritable_s0: u32,
-    effective_s1: u32,
-    permitted_s1: u32,
-    inheritable_s1: u32,
+impl From<&BaseState> for raw::BaseMasks {
+    fn from(state: &BaseState) -> Self {
+        Self {
+            effective: state.effective.raw(),
+            permitted: state.permitted.raw(),
+            inheritable: state.inheritable.raw(),
+        }
+    }
 }
@@ -0,0 +1,610 @@
+//! Implementation of a compact capabilities set.
+
+use crate::errors::{CapsError, ErrorKind};
+use crate::{Capability, CapsHashSet, ALL_CAPS};
+use std::iter::FromIterator;
+use std::ops::{
+    BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not, Sub, SubAssign,
+};
+
+/// Policy for mask bits which do not map to a capability known to this library.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum UnknownBits {
+    /// Keep unknown bits in the set, so that the mask round-trips unchanged.
+    Keep,
+    /// Reject masks with unknown bits set.
+    Reject,
+}
+
+/// A set of capabilities, backed by a 64-bit mask.
+///
+/// This is a `Copy` alternative to `CapsHashSet`, which never allocates.
+/// Each capability is stored at the bit given by `Capability::bitmask()`,
+/// i.e. with the same layout the kernel uses.
+///
+/// As text, a set is formatted as a 16-digit hexadecimal mask, in the
+/// same way as `/proc/<pid>/status` and `capsh --decode`:
+///
+/// ```rust
+/// use caps::{Capability, CapsBitSet};
+///
+/// let set: CapsBitSet = "0000000000003000".parse().unwrap();
+/// assert!(set.contains(Capability::CAP_NET_RAW));
+/// assert_eq!(set.to_string(), "0000000000003000");
+/// ```
+///
+/// A set may carry bits for capabilities unknown to this library, as
+/// added by newer kernels. Those are read from the kernel, and preserved by
+/// `to_mask()` and set operations, and counted by `len()`. They are skipped
+/// by `iter()` and `len_known()`, but can be inspected through `indices()`
+/// and `unknown()`.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, Default)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct CapsBitSet(u64);
+
+impl CapsBitSet {
+    /// Mask of all capabilities supported by this library.
+    pub(crate) const ALL_MASK: u64 = (1u64 << ALL_CAPS.len()) - 1;
+
+    /// Return an empty set.
+    pub const fn new() -> Self {
+        Self(0)
+    }
+
+    /// Return the set of all capabilities supported by this library.
+    pub const fn all() -> Self {
+        Self(Self::ALL_MASK)
+    }
+
+    /// Return the set of all capabilities with index up to `last_cap`.
+    ///
+    /// This includes capabilities unknown to this library, for example
+    /// to build the set of capabilities supported by the running kernel.
+    pub const fn up_to(last_cap: u8) -> Self {
+        if last_cap >= 63 {
+            Self(u64::MAX)
+        } else {
+            Self((1u64 << (last_cap + 1)) - 1)
+        }
+    }
+
+    /// Build a set from a raw kernel mask, keeping unknown bits.
+    pub(crate) const fn from_raw(mask: u64) -> Self {
+        Self(mask)
+    }
+
+    /// Return the raw kernel mask for this set.
+    pub(crate) const fn raw(&self) -> u64 {
+        self.0
+    }
+
+    /// Build a set from a kernel-layout mask.
+    ///
+    /// Bits which do not map to a capability known to this library are
+    /// handled according to `unknown`.
+    pub fn from_mask(mask: u64, unknown: UnknownBits) -> Result<Self, CapsError> {
+        let extra = mask & !Self::ALL_MASK;
+        if extra != 0 && unknown == UnknownBits::Reject {
+            return Err(CapsError::new(
+                ErrorKind::InvalidCapability,
+                format!(
+                    "mask {:016x} has unknown capabilities bits {:#x}",
+                    mask, extra
+                ),
+            ));
+        }
+        Ok(Self(mask))
+    }
+
+    /// Return the kernel-layout mask for this set, including unknown bits.
+    pub const fn to_mask(&self) -> u64 {
+        self.0
+    }
+
+    /// Parse a hexadecimal mask, with an optional `0x` prefix.
+    ///
+    /// Bits which do not map to a capability known to this library are
+    /// handled according to `unknown`.
+    pub fn from_hex(s: &str, unknown: UnknownBits) -> Result<Self, CapsError> {
+        let digits = s
+            .strip_prefix("0x")
+            .or_else(|| s.strip_prefix("0X"))
+            .unwrap_or(s);
+        // `from_str_radix` would accept a sign.
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(CapsError::new(
+                ErrorKind::InvalidData,
+                format!("invalid capabilities mask '{}'", s),
+            ));
+        }
+        let mask = u64::from_str_radix(digits, 16).map_err(|e| {
+            CapsError::new(
+                ErrorKind::InvalidData,
+                format!("invalid capabilities mask '{}': {}", s, e),
+            )
+        })?;
+        Self::from_mask(mask, unknown)
+    }
+
+    /// Add a capability to this set.
+    ///
+    /// Returns whether the capability was newly inserted.
+    pub fn insert(&mut self, cap: Capability) -> bool {
+        let absent = !self.contains(cap);
+        self.0 |= cap.bitmask();
+        absent
+    }
+
+    /// Remove a capability from this set.
+    ///
+    /// Returns whether the capability was present in the set.
+    pub fn remove(&mut self, cap: Capability) -> bool {
+        let present = self.contains(cap);
+        self.0 &= !cap.bitmask();
+        present
+    }
+
+    /// Return whether this set contains a capability.
+    pub fn contains(&self, cap: Capability) -> bool {
+        (self.0 & cap.bitmask()) != 0
+    }
+
+    /// Add the capability with kernel index `index`, even if unknown to this library.
+    ///
+    /// Returns whether the capability was newly inserted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not lower than 64.
+    pub fn insert_index(&mut self, index: u8) -> bool {
+        assert!(index < 64, "capability index {} out of range", index);
+        let absent = !self.contains_index(index);
+        self.0 |= 1u64 << index;
+        absent
+    }
+
+    /// Remove the capability with kernel index `index`.
+    ///
+    /// Returns whether the capability was present in the set.
+    pub fn remove_index(&mut self, index: u8) -> bool {
+        let present = self.contains_index(index);
+        if present {
+            self.0 &= !(1u64 << index);
+        }
+        present
+    }
+
+    /// Return whether this set contains the capability with kernel index `index`.
+    pub fn contains_index(&self, index: u8) -> bool {
+        index < 64 && (self.0 & (1u64 << index)) != 0
+    }
+
+    /// Return the capabilities in this set which are unknown to this library.
+    pub fn unknown(&self) -> Self {
+        Self(self.0 & !Self::ALL_MASK)
+    }
+
+    /// Return the number of capabilities in this set, including unknown ones.
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    /// Return the number of capabilities in this set known to this library.
+    ///
+    /// This is the number of items yielded by `iter()`.
+    pub fn len_known(&self) -> usize {
+        (self.0 & Self::ALL_MASK).count_ones() as usize
+    }
+
+    /// Return whether this set is empty, including unknown capabilities.
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Remove all capabilities from this set.
+    pub fn clear(&mut self) {
+        self.0 = 0;
+    }
+
+    /// Return whether all capabilities in this set are also in `other`.
+    pub fn is_subset(&self, other: &Self) -> bool {
+        (self.0 & !other.0) == 0
+    }
+
+    /// Return whether all capabilities in `other` are also in this set.
+    pub fn is_superset(&self, other: &Self) -> bool {
+        other.is_subset(self)
+    }
+
+    /// Return whether this set and `other` have no capabilities in common.
+    pub fn is_disjoint(&self, other: &Self) -> bool {
+        (self.0 & other.0) == 0
+    }
+
+    /// Return the complement of this set within `universe`.
+    ///
+    /// The `!` operator complements against all capabilities supported by
+    /// this library. This can be used instead to complement against the
+    /// capabilities supported by the running kernel, for example:
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), caps::errors::CapsError> {
+    /// use caps::{runtime, CapSet, CapsBitSet};
+    ///
+    /// let supported = CapsBitSet::from(runtime::procfs_all_supported(None)?);
+    /// let effective = caps::read_bits(None, CapSet::Effective)?;
+    /// let missing = effective.complement_in(supported);
+    /// assert!(missing.is_disjoint(&effective));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn complement_in(self, universe: Self) -> Self {
+        universe - self
+    }
+
+    /// Return an iterator over the known capabilities in this set, in index order.
+    pub fn iter(&self) -> CapsBitSetIter {
+        CapsBitSetIter(self.0 & Self::ALL_MASK)
+    }
+
+    /// Return an iterator over the kernel indices of all capabilities in this set.
+    ///
+    /// Unlike `iter()`, this also yields capabilities unknown to this library.
+    pub fn indices(&self) -> CapsBitSetIndices {
+        CapsBitSetIndices(self.0)
+    }
+}
+
+/// Format as a 16-digit hexadecimal mask, including unknown bits.
+impl std::fmt::Display for CapsBitSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// Parse a hexadecimal mask, keeping unknown bits.
+impl std::str::FromStr for CapsBitSet {
+    type Err = CapsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_hex(s, UnknownBits::Keep)
+    }
+}
+
+impl BitOr for CapsBitSet {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for CapsBitSet {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAnd for CapsBitSet {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl BitAndAssign for CapsBitSet {
+    fn bitand_assign(&mut self, rhs: Self) {
+        self.0 &= rhs.0;
+    }
+}
+
+impl BitXor for CapsBitSet {
+    type Output = Self;
+
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        Self(self.0 ^ rhs.0)
+    }
+}
+
+impl BitXorAssign for CapsBitSet {
+    fn bitxor_assign(&mut self, rhs: Self) {
+        self.0 ^= rhs.0;
+    }
+}
+
+impl Sub for CapsBitSet {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self(self.0 & !rhs.0)
+    }
+}
+
+impl SubAssign for CapsBitSet {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.0 &= !rhs.0;
+    }
+}
+
+/// Complement against all capabilities supported by this library.
+impl Not for CapsBitSet {
+    type Output = Self;
+
+    fn not(self) -> Self::Output {
+        self.complement_in(Self::all())
+    }
+}
+
+/// Iterator over the capabilities in a `CapsBitSet`.
+#[derive(Debug, Clone)]
+pub struct CapsBitSetIter(u64);
+
+impl Iterator for CapsBitSetIter {
+    type Item = Capability;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0 == 0 {
+            return None;
+        }
+        let index = self.0.trailing_zeros() as usize;
+        self.0 &= self.0 - 1;
+        Some(ALL_CAPS[index])
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.0.count_ones() as usize;
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for CapsBitSetIter {}
+
+/// Iterator over the capabilities indices in a `CapsBitSet`.
+#[derive(Debug, Clone)]
+pub struct CapsBitSetIndices(u64);
+
+impl Iterator for CapsBitSetIndices {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0 == 0 {
+            return None;
+        }
+        let index = self.0.trailing_zeros() as u8;
+        self.0 &= self.0 - 1;
+        Some(index)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.0.count_ones() as usize;
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for CapsBitSetIndices {}
+
+impl IntoIterator for CapsBitSet {
+    type Item = Capability;
+    type IntoIter = CapsBitSetIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for &CapsBitSet {
+    type Item = Capability;
+    type IntoIter = CapsBitSetIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl FromIterator<Capability> for CapsBitSet {
+    fn from_iter<I: IntoIterator<Item = Capability>>(iter: I) -> Self {
+        let mut set = Self::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<'a> FromIterator<&'a Capability> for CapsBitSet {
+    fn from_iter<I: IntoIterator<Item = &'a Capability>>(iter: I) -> Self {
+        iter.into_iter().copied().collect()
+    }
+}
+
+impl Extend<Capability> for CapsBitSet {
+    fn extend<I: IntoIterator<Item = Capability>>(&mut self, iter: I) {
+        for c in iter {
+            self.insert(c);
+        }
+    }
+}
+
+impl From<Capability> for CapsBitSet {
+    fn from(cap: Capability) -> Self {
+        Self(cap.bitmask())
+    }
+}
+
+impl From<&CapsHashSet> for CapsBitSet {
+    fn from(set: &CapsHashSet) -> Self {
+        set.iter().collect()
+    }
+}
+
+impl From<CapsHashSet> for CapsBitSet {
+    fn from(set: CapsHashSet) -> Self {
+        Self::from(&set)
+    }
+}
+
+impl From<CapsBitSet> for CapsHashSet {
+    fn from(set: CapsBitSet) -> Self {
+        set.iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_remove() {
+        let mut set = CapsBitSet::new();
+        assert!(set.is_empty());
+        assert!(set.insert(Capability::CAP_CHOWN));
+        assert!(!set.insert(Capability::CAP_CHOWN));
+        assert!(set.insert(Capability::CAP_CHECKPOINT_RESTORE));
+        assert_eq!(set.len(), 2);
+        assert!(set.contains(Capability::CAP_CHECKPOINT_RESTORE));
+        assert!(set.remove(Capability::CAP_CHOWN));
+        assert!(!set.remove(Capability::CAP_CHOWN));
+        assert_eq!(set.len(), 1);
+        set.clear();
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn test_iter_order() {
+        let set: CapsBitSet = [
+            Capability::CAP_BPF,
+            Capability::CAP_KILL,
+            Capability::CAP_CHOWN,
+        ]
+        .iter()
+        .collect();
+        let caps: Vec<_> = set.iter().collect();
+        assert_eq!(
+            caps,
+            vec![
+                Capability::CAP_CHOWN,
+                Capability::CAP_KILL,
+                Capability::CAP_BPF
+            ]
+        );
+        assert_eq!(set.iter().len(), 3);
+    }
+
+    #[test]
+    fn test_hashset_roundtrip() {
+        let all = CapsBitSet::all();
+        assert_eq!(all.len(), crate::all().len());
+        let hs = CapsHashSet::from(all);
+        assert_eq!(hs, crate::all());
+        assert_eq!(CapsBitSet::from(&hs), all);
+        for c in crate::all() {
+            assert_eq!(CapsBitSet::from(c).raw(), c.bitmask());
+        }
+    }
+
+    #[test]
+    fn test_set_algebra() {
+        let a: CapsBitSet = [Capability::CAP_CHOWN, Capability::CAP_KILL]
+            .iter()
+            .collect();
+        let b: CapsBitSet = [Capability::CAP_KILL, Capability::CAP_BPF].iter().collect();
+        let kill = CapsBitSet::from(Capability::CAP_KILL);
+
+        assert_eq!((a | b).len(), 3);
+        assert_eq!(a & b, kill);
+        assert_eq!(a - b, CapsBitSet::from(Capability::CAP_CHOWN));
+        assert_eq!(a ^ b, (a | b) - kill);
+        assert_eq!(!a & a, CapsBitSet::new());
+        assert_eq!(!a | a, CapsBitSet::all());
+        assert_eq!(!CapsBitSet::all(), CapsBitSet::new());
+        assert_eq!(a.complement_in(b), CapsBitSet::from(Capability::CAP_BPF));
+
+        let mut c = a;
+        c |= b;
+        c -= kill;
+        c &= a;
+        assert_eq!(c, CapsBitSet::from(Capability::CAP_CHOWN));
+        c ^= kill;
+        assert_eq!(c, a);
+    }
+
+    #[test]
+    fn test_subset() {
+        let a = CapsBitSet::from(Capability::CAP_CHOWN);
+        let all = CapsBitSet::all();
+        assert!(a.is_subset(&all));
+        assert!(all.is_superset(&a));
+        assert!(!all.is_subset(&a));
+        assert!(CapsBitSet::new().is_subset(&a));
+        assert!(a.is_disjoint(&!a));
+        assert!(!a.is_disjoint(&all));
+    }
+
+    #[test]
+    fn test_mask_unknown_bits() {
+        let mask = 0x0000_03ff_ffff_ffff;
+        let kept = CapsBitSet::from_mask(mask, UnknownBits::Keep).unwrap();
+        assert_eq!(kept.to_mask(), mask);
+        assert_eq!(kept.len(), 42);
+        assert_eq!(kept.len_known(), CapsBitSet::all().len());
+        assert_eq!(kept.iter().count(), kept.len_known());
+        assert_eq!(kept & CapsBitSet::all(), CapsBitSet::all());
+        let err = CapsBitSet::from_mask(mask, UnknownBits::Reject).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidCapability);
+        let all = CapsBitSet::from_mask(CapsBitSet::ALL_MASK, UnknownBits::Reject).unwrap();
+        assert_eq!(all, CapsBitSet::all());
+    }
+
+    #[test]
+    fn test_hex() {
+        let set: CapsBitSet = "000001ffffffffff".parse().unwrap();
+        assert_eq!(set, CapsBitSet::all());
+        assert_eq!(set.to_string(), "000001ffffffffff");
+        assert_eq!(CapsBitSet::new().to_string(), "0000000000000000");
+        let net: CapsBitSet = "0x3000".parse().unwrap();
+        assert_eq!(
+            net,
+            [Capability::CAP_NET_ADMIN, Capability::CAP_NET_RAW]
+                .iter()
+                .collect()
+        );
+        let unknown: CapsBitSet = "ffffffffffffffff".parse().unwrap();
+        assert_eq!(unknown.to_string(), "ffffffffffffffff");
+        assert!(CapsBitSet::from_hex("ffffffffffffffff", UnknownBits::Reject).is_err());
+        for bad in &["", "0x", "+1", "-1", "xyz", "1ffffffffffffffff", "00 1"] {
+            let err = bad.parse::<CapsBitSet>().unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_unknown_indices() {
+        let mut set = CapsBitSet::from_raw(u64::MAX);
+        assert_eq!(set.len_known(), CapsBitSet::all().len());
+        assert_eq!(set.indices().len(), set.len());
+        assert_eq!(set.len(), 64);
+        let only_unknown = CapsBitSet::from_raw(1 << 41);
+        assert!(!only_unknown.is_empty());
+        assert_eq!(only_unknown.len(), 1);
+        assert_eq!(only_unknown.len_known(), 0);
+        assert_eq!(set.unknown(), set - CapsBitSet::all());
+        assert!(set.contains_index(63));
+        assert!(!set.contains_index(64));
+        assert!(set.remove_index(41));
+        assert!(!set.remove_index(41));
+        assert!(!set.remove_index(200));
+        assert!(set.insert_index(41));
+        assert_eq!(set.to_mask(), u64::MAX);
+
+        let kernel = CapsBitSet::up_to(42);
+        assert_eq!(kernel.unknown().indices().collect::<Vec<_>>(), vec![41, 42]);
+        assert_eq!(CapsBitSet::up_to(40), CapsBitSet::all());
+        assert_eq!(CapsBitSet::up_to(63).to_mask(), u64::MAX);
+        assert_eq!(
+            CapsBitSet::up_to(0),
+            CapsBitSet::from(Capability::CAP_CHOWN)
+        );
+    }
+}
@@ -1,46 +1,49 @@
-use crate::errors::CapsError;
-use crate::nr;
-use crate::Capability;
-use std::io::Error;
+use crate::errors::{CapsError, ErrorKind};
+use crate::raw;
+use crate::runtime;
+use crate::{CapSet, Capability, CapsBitSet, CapsHashSet};
 
 pub fn clear() -> Result<(), CapsError> {
-    for c in super::all() {
-        if has_cap(c).unwrap_or(false) {
-            drop(c)?;
-        }
+    for index in read_bits()?.indices() {
+        drop_index(index)?;
     }
     Ok(())
 }
 
 pub fn drop(cap: Capability) -> Result<(), CapsError> {
-    let ret = unsafe { libc::prctl(nr::PR_CAPBSET_DROP, libc::c_uint::from(cap.index()), 0, 0) };
-    match ret {
-        0 => Ok(()),
-        _ => Err(CapsError::from(format!(
-            "PR_CAPBSET_DROP failure: {}",
-            Error::last_os_error()
-        ))),
-    }
+    drop_index(cap.index()).map_err(|e| e.with_cap(cap))
+}
+
+/// Drop a capability by index, even if unknown to this library.
+pub fn drop_index(index: u8) -> Result<(), CapsError> {
+    raw::bounding_drop(index).map_err(|e| index_error("PR_CAPBSET_DROP", e))
 }
 
 pub fn has_cap(cap: Capability) -> Result<bool, CapsError> {
-    let ret = unsafe { libc::prctl(nr::PR_CAPBSET_READ, libc::c_uint::from(cap.index()), 0, 0) };
-    match ret {
-        0 => Ok(false),
-        1 => Ok(true),
-        _ => Err(CapsError::from(format!(
-            "PR_CAPBSET_READ failure: {}",
-            Error::last_os_error()
-        ))),
-    }
+    has_index(cap.index()).map_err(|e| e.with_cap(cap))
 }
 
-pub fn read() -> Result<super::CapsHashSet, CapsError> {
-    let mut res = super::CapsHashSet::new();
-    for c in super::all() {
-        if has_cap(c).unwrap_or(false) {
-            res.insert(c);
-        }
-    }
-    Ok(res)
+/// Check a capability by index, even if unknown to this library.
+///
+/// This fails with `EINVAL` past the last capability supported by the kernel.
+pub fn has_index(index: u8) -> Result<bool, CapsError> {
+    raw::bounding_has(index).map_err(|e| index_error("PR_CAPBSET_READ", e))
+}
+
+pub fn read() -> Result<CapsHashSet, CapsError> {
+    read_bits().map(CapsHashSet::from)
+}
+
+/// Read the Bounding set, including capabilities unknown to this library.
+pub fn read_bits() -> Result<CapsBitSet, CapsError> {
+    let mask =
+        raw::bounding_read(runtime::last_cap()?).map_err(|e| index_error("PR_CAPBSET_READ", e))?;
+    Ok(CapsBitSet::from_raw(mask))
+}
+
+/// Build the error for a failed per-capability bounding operation.
+fn index_error(op: &str, errno: i32) -> CapsError {
+    CapsError::from_errno(op, errno)
+        .on_einval(ErrorKind::InvalidCapability)
+        .with_set(CapSet::Bounding)
 }
@@ -0,0 +1,188 @@
+/*!
+Configure capabilities of child processes.
+
+This module exposes an extension trait for `std::process::Command`,
+which sets up capabilities of the child process right before it
+executes the new program. The configuration is fully computed when
+it is attached to the command, and applied in the child (between
+`fork(2)` and `execve(2)`) with raw syscalls only, without allocating.
+
+## Example
+
+```rust
+use caps::command::{ChildCaps, CommandCapsExt};
+use caps::Capability;
+use std::process::Command;
+
+// Let `ping` run with CAP_NET_RAW, inherited through the Ambient set.
+let mut cmd = Command::new("ping");
+cmd.arg("-c1").arg("127.0.0.1");
+cmd.child_caps(ChildCaps::new().ambient(Capability::CAP_NET_RAW));
+```
+*/
+
+use crate::securebits::SecureBits;
+use crate::{base, raw, runtime};
+use crate::{Capability, CapsBitSet};
+use std::io;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+/// Capabilities configuration for a child process.
+///
+/// Only the configured settings are changed in the child, all others
+/// are inherited from the parent thread as usual. Settings are applied
+/// in an order the kernel accepts:
+///  1. the Inheritable set (extended with the Ambient set, if configured),
+///  2. the Ambient set,
+///  3. the Bounding set,
+///  4. securebits,
+///  5. the "no new privileges" flag.
+///
+/// Changing the Bounding set or securebits requires `CAP_SETPCAP` in the
+/// Permitted set of the parent.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct ChildCaps {
+    inheritable: Option<CapsBitSet>,
+    ambient: Option<CapsBitSet>,
+    bounding: Option<CapsBitSet>,
+    securebits: Option<SecureBits>,
+    no_new_privs: bool,
+}
+
+impl ChildCaps {
+    /// Return an empty configuration, which leaves capabilities untouched.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the Inheritable set of the child.
+    pub fn inheritable<S: Into<CapsBitSet>>(mut self, set: S) -> Self {
+        self.inheritable = Some(set.into());
+        self
+    }
+
+    /// Set the Ambient set of the child.
+    ///
+    /// Capabilities in `set` are also raised in the Inheritable set, and
+    /// must be in the Permitted set of the parent.
+    pub fn ambient<S: Into<CapsBitSet>>(mut self, set: S) -> Self {
+        self.ambient = Some(set.into());
+        self
+    }
+
+    /// Reduce the Bounding set of the child to `set`.
+    pub fn bounding<S: Into<CapsBitSet>>(mut self, set: S) -> Self {
+        self.bounding = Some(set.into());
+        self
+    }
+
+    /// Set securebits flags of the child to `bits`.
+    pub fn securebits(mut self, bits: SecureBits) -> Self {
+        self.securebits = Some(bits);
+        self
+    }
+
+    /// Set the "no new privileges" flag of the child.
+    pub fn no_new_privs(mut self, set: bool) -> Self {
+        self.no_new_privs = set;
+        self
+    }
+}
+
+/// Extension trait to configure capabilities of a `Command` child process.
+pub trait CommandCapsExt {
+    /// Apply `caps` to the child process, before it executes the program.
+    ///
+    /// Kernel features are detected when this is called. If the
+    /// configuration cannot be applied, spawning fails with the
+    /// corresponding OS error.
+    fn child_caps(&mut self, caps: ChildCaps) -> &mut Self;
+}
+
+impl CommandCapsExt for Command {
+    fn child_caps(&mut self, caps: ChildCaps) -> &mut Self {
+        let prepared = Prepared::new(caps);
+        // Safety: the closure only performs raw syscalls on plain data,
+        // without allocating or taking locks.
+        unsafe { self.pre_exec(move || prepared.apply()) }
+    }
+}
+
+/// Configuration ready to be applied in a forked child.
+#[derive(Debug, Clone, Copy)]
+struct Prepared {
+    caps: ChildCaps,
+    /// `capget(2)` ABI version to use.
+    version: u32,
+    /// Whether the kernel supports the requested sets.
+    supported: Result<(), i32>,
+    /// Index of the last capability, only detected if Bounding set is configured.
+    last_cap: Result<u8, i32>,
+}
+
+impl Prepared {
+    fn new(caps: ChildCaps) -> Self {
+        let supported = match caps.ambient {
+            Some(_) if !runtime::has_ambient() => Err(libc::EINVAL),
+            _ => Ok(()),
+        };
+        let last_cap = match caps.bounding {
+            Some(_) => runtime::last_cap().map_err(|e| e.errno().unwrap_or(libc::ENOSYS)),
+            None => Err(libc::EINVAL),
+        };
+        Self {
+            caps,
+            version: base::version(),
+            supported,
+            last_cap,
+        }
+    }
+
+    fn apply(&self) -> io::Result<()> {
+        self.apply_raw().map_err(io::Error::from_raw_os_error)
+    }
+
+    fn apply_raw(&self) -> Result<(), i32> {
+        self.supported?;
+        let version = self.version;
+        let caps = &self.caps;
+
+        // Raise Inheritable (and CAP_SETPCAP in Effective, if needed).
+        // The Effective set is recomputed on `execve(2)` anyway.
+        let current = raw::capget(version, 0)?;
+        let mut masks = current;
+        if let Some(i) = caps.inheritable {
+            masks.inheritable = i.raw();
+        }
+        if let Some(a) = caps.ambient {
+            masks.inheritable |= a.raw();
+        }
+        let setpcap = Capability::CAP_SETPCAP.bitmask();
+        if (caps.bounding.is_some() || caps.securebits.is_some())
+            && (current.permitted & setpcap) != 0
+        {
+            masks.effective |= setpcap;
+        }
+        if masks != current {
+            raw::capset(version, 0, &masks)?;
+        }
+
+        if let Some(a) = caps.ambient {
+            raw::ambient_clear()?;
+            for index in a.indices() {
+                raw::ambient_raise(index)?;
+            }
+        }
+        if let Some(b) = caps.bounding {
+            raw::bounding_restrict(b.raw(), self.last_cap?)?;
+        }
+        if let Some(s) = caps.securebits {
+            raw::set_securebits(s.bits())?;
+        }
+        if caps.no_new_privs {
+            raw::set_no_new_privs()?;
+        }
+        Ok(())
+    }
+}
@@ -1,25 +1,186 @@
 //! Error handling.
 
+use crate::{CapSet, Capability};
+use std::io;
+
+/// Category of a library error.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The caller lacks the privileges for the operation (`EPERM`, `EACCES`).
+    PermissionDenied,
+    /// The operation is not supported on this target or for this set.
+    NotSupported,
+    /// The capability is unknown to this library or to the running kernel.
+    InvalidCapability,
+    /// An argument is not valid for the operation.
+    InvalidInput,
+    /// Some data (text, extended attribute, procfs content) is malformed.
+    InvalidData,
+    /// The running kernel lacks support for the operation.
+    KernelTooOld,
+    /// procfs is not mounted or not accessible.
+    ProcfsUnavailable,
+    /// The change is blocked by a locked securebits flag.
+    Locked,
+    /// The target thread, process or file does not exist.
+    NotFound,
+    /// The target file is a symbolic link, which cannot carry capabilities.
+    SymbolicLink,
+    /// Any other error.
+    Other,
+}
+
+impl ErrorKind {
+    fn from_errno(errno: i32) -> Self {
+        match errno {
+            libc::EPERM | libc::EACCES => ErrorKind::PermissionDenied,
+            libc::EINVAL => ErrorKind::InvalidInput,
+            libc::ENOTSUP | libc::ENOSYS => ErrorKind::NotSupported,
+            libc::ENOENT | libc::ESRCH | libc::ENODATA => ErrorKind::NotFound,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
 /// Library errors.
+///
+/// Beside a human-readable message, errors carry an [`ErrorKind`], the
+/// underlying OS error (if any, also available through `source()`), and
+/// the capability, set and thread involved in the failing operation.
+///
+/// [`ErrorKind`]: enum.ErrorKind.html
 #[derive(Debug)]
-pub struct CapsError(pub(crate) String);
+pub struct CapsError {
+    pub(crate) kind: ErrorKind,
+    pub(crate) message: String,
+    pub(crate) source: Option<io::Error>,
+    pub(crate) cap: Option<Capability>,
+    pub(crate) set: Option<CapSet>,
+    pub(crate) tid: Option<i32>,
+}
+
+impl CapsError {
+    pub(crate) fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            source: None,
+            cap: None,
+            set: None,
+            tid: None,
+        }
+    }
+
+    /// Build an error from an I/O failure, with a kind based on its errno.
+    pub(crate) fn io(context: impl std::fmt::Display, err: io::Error) -> Self {
+        let kind = match err.raw_os_error() {
+            Some(errno) => ErrorKind::from_errno(errno),
+            None if err.kind() == io::ErrorKind::NotFound => ErrorKind::NotFound,
+            None => ErrorKind::Other,
+        };
+        let mut res = Self::new(kind, format!("{}: {}", context, err));
+        res.source = Some(err);
+        res
+    }
+
+    /// Build an error from an `errno` value, after `op` failed.
+    pub(crate) fn from_errno(op: &str, errno: i32) -> Self {
+        let err = io::Error::from_raw_os_error(errno);
+        Self::io(format_args!("{} failure", op), err)
+    }
+
+    /// Use `kind` if the underlying OS error is `EINVAL`.
+    ///
+    /// Syscalls and prctl options report both unknown arguments and
+    /// unknown operations through `EINVAL`, which callers can disambiguate.
+    pub(crate) fn on_einval(mut self, kind: ErrorKind) -> Self {
+        if self.errno() == Some(libc::EINVAL) {
+            self.kind = kind;
+        }
+        self
+    }
+
+    /// Override the category of this error.
+    pub(crate) fn with_kind(mut self, kind: ErrorKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Prepend `prefix` to the message.
+    pub(crate) fn wrap(mut self, prefix: impl std::fmt::Display) -> Self {
+        self.message = format!("{}: {}", prefix, self.message);
+        self
+    }
+
+    /// Record the capability involved, unless already known.
+    pub(crate) fn with_cap(mut self, cap: Capability) -> Self {
+        self.cap = self.cap.or(Some(cap));
+        self
+    }
+
+    /// Record the set involved, unless already known.
+    pub(crate) fn with_set(mut self, set: CapSet) -> Self {
+        self.set = self.set.or(Some(set));
+        self
+    }
+
+    /// Record the thread involved, unless already known.
+    pub(crate) fn with_tid(mut self, tid: i32) -> Self {
+        self.tid = self.tid.or(Some(tid));
+        self
+    }
+
+    /// Return the category of this error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Return the raw OS error code, if this error comes from a syscall.
+    pub fn errno(&self) -> Option<i32> {
+        self.source.as_ref().and_then(io::Error::raw_os_error)
+    }
+
+    /// Return the capability involved in the failing operation, if any.
+    pub fn capability(&self) -> Option<Capability> {
+        self.cap
+    }
+
+    /// Return the set involved in the failing operation, if any.
+    pub fn set(&self) -> Option<CapSet> {
+        self.set
+    }
+
+    /// Return the thread involved in the failing operation, if any.
+    ///
+    /// A value of 0 stands for the calling thread.
+    pub fn tid(&self) -> Option<i32> {
+        self.tid
+    }
+}
 
 impl From<&str> for CapsError {
     fn from(arg: &str) -> Self {
-        Self(arg.to_string())
+        Self::new(ErrorKind::Other, arg)
     }
 }
 
 impl From<String> for CapsError {
     fn from(arg: String) -> Self {
-        Self(arg)
+        Self::new(ErrorKind::Other, arg)
     }
 }
 
-impl std::error::Error for CapsError {}
+impl std::error::Error for CapsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|e| e as &(dyn std::error::Error + 'static))
+    }
+}
 
 impl std::fmt::Display for CapsError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "caps error: {}", self.0)
+        write!(f, "caps error: {}", self.message)
     }
 }
@@ -0,0 +1,428 @@
+/*!
+Manipulate file capabilities.
+
+This module exposes methods to inspect and manipulate capabilities
+attached to files, as stored by the kernel in the `security.capability`
+extended attribute. See `capabilities(7)`.
+
+The on-disk format can also be handled without touching any filesystem,
+through [`FileCaps::from_bytes`] and [`FileCaps::to_bytes`].
+
+Errors from [`set`] and [`remove`] report their cause through
+`CapsError::kind()`:
+ * `ErrorKind::SymbolicLink`: the target is a symbolic link,
+ * `ErrorKind::PermissionDenied`: `CAP_SETFCAP` is missing,
+ * `ErrorKind::NotSupported`: the filesystem lacks extended attributes support.
+
+[`FileCaps::from_bytes`]: struct.FileCaps.html#method.from_bytes
+[`FileCaps::to_bytes`]: struct.FileCaps.html#method.to_bytes
+[`set`]: fn.set.html
+[`remove`]: fn.remove.html
+
+## Example
+
+```rust
+let fcaps = caps::file::read("/usr/bin/ping");
+match fcaps {
+    Ok(Some(fc)) => println!("File capabilities: {:?}", fc.permitted),
+    Ok(None) => println!("No file capabilities"),
+    Err(e) => println!("Failed to read file capabilities: {}", e),
+}
+```
+*/
+
+use crate::errors::{CapsError, ErrorKind};
+use crate::nr;
+use crate::CapsBitSet;
+use std::ffi::CString;
+use std::io::Error;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+/// Capabilities attached to a file.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct FileCaps {
+    /// File permitted set, including capabilities unknown to this library.
+    pub permitted: CapsBitSet,
+    /// File inheritable set, including capabilities unknown to this library.
+    pub inheritable: CapsBitSet,
+    /// Whether permitted capabilities are raised in the effective set on `execve(2)`.
+    pub effective: bool,
+    /// Root user ID of the owning user namespace (`VFS_CAP_REVISION_3` only).
+    pub rootid: Option<u32>,
+}
+
+impl FileCaps {
+    /// Decode the content of a `security.capability` extended attribute.
+    ///
+    /// All `VFS_CAP_REVISION_1`, `VFS_CAP_REVISION_2` and `VFS_CAP_REVISION_3`
+    /// layouts are supported. The input must exactly match the length of its
+    /// revision, and must not have unknown flags set. Capabilities unknown
+    /// to this library are kept, so that decoding is never lossy.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, CapsError> {
+        if buf.len() < 4 {
+            return Err(CapsError::new(
+                ErrorKind::InvalidData,
+                format!("invalid file capabilities: {} bytes", buf.len()),
+            ));
+        }
+        let word = |i: usize| -> u32 {
+            let off = i * 4;
+            u32::from_le_bytes([buf[off], buf[off + 1], buf[off + 2], buf[off + 3]])
+        };
+        let magic = word(0);
+        let revision = magic & nr::VFS_CAP_REVISION_MASK;
+        let (size, words) = match revision {
+            nr::VFS_CAP_REVISION_1 => (nr::XATTR_CAPS_SZ_1, 1),
+            nr::VFS_CAP_REVISION_2 => (nr::XATTR_CAPS_SZ_2, 2),
+            nr::VFS_CAP_REVISION_3 => (nr::XATTR_CAPS_SZ_3, 2),
+            _ => {
+                return Err(CapsError::new(
+                    ErrorKind::InvalidData,
+                    format!("unknown file capabilities revision {:#010x}", revision),
+                ))
+            }
+        };
+        if buf.len() != size {
+            return Err(CapsError::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "invalid file capabilities: {} bytes for revision {:#010x}, expected {}",
+                    buf.len(),
+                    revision,
+                    size
+                ),
+            ));
+        }
+        let flags = magic & !nr::VFS_CAP_REVISION_MASK;
+        if (flags & !nr::VFS_CAP_FLAGS_EFFECTIVE) != 0 {
+            return Err(CapsError::new(
+                ErrorKind::InvalidData,
+                format!("unknown file capabilities flags {:#08x}", flags),
+            ));
+        }
+        let (mut permitted, mut inheritable) = (0u64, 0u64);
+        for i in 0..words {
+            permitted |= u64::from(word(1 + 2 * i)) << (32 * i);
+            inheritable |= u64::from(word(2 + 2 * i)) << (32 * i);
+        }
+        let rootid = match revision {
+            nr::VFS_CAP_REVISION_3 => Some(word(5)),
+            _ => None,
+        };
+        Ok(Self {
+            permitted: CapsBitSet::from_raw(permitted),
+            inheritable: CapsBitSet::from_raw(inheritable),
+            effective: (flags & nr::VFS_CAP_FLAGS_EFFECTIVE) != 0,
+            rootid,
+        })
+    }
+
+    /// Encode the content of a `security.capability` extended attribute.
+    ///
+    /// This uses the `VFS_CAP_REVISION_2` layout, or `VFS_CAP_REVISION_3`
+    /// if `rootid` is set, as `setcap(8)` does.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let permitted = self.permitted.raw();
+        let inheritable = self.inheritable.raw();
+        let (mut magic, size) = match self.rootid {
+            None => (nr::VFS_CAP_REVISION_2, nr::XATTR_CAPS_SZ_2),
+            Some(_) => (nr::VFS_CAP_REVISION_3, nr::XATTR_CAPS_SZ_3),
+        };
+        if self.effective {
+            magic |= nr::VFS_CAP_FLAGS_EFFECTIVE;
+        }
+        let mut buf = Vec::with_capacity(size);
+        buf.extend_from_slice(&magic.to_le_bytes());
+        for i in 0..2 {
+            buf.extend_from_slice(&((permitted >> (32 * i)) as u32).to_le_bytes());
+            buf.extend_from_slice(&((inheritable >> (32 * i)) as u32).to_le_bytes());
+        }
+        if let Some(rootid) = self.rootid {
+            buf.extend_from_slice(&rootid.to_le_bytes());
+        }
+        buf
+    }
+}
+
+/// Read capabilities of the file at `path`.
+///
+/// Symbolic links are followed. If the file has no capabilities
+/// attached, this returns `Ok(None)`.
+pub fn read<P: AsRef<Path>>(path: P) -> Result<Option<FileCaps>, CapsError> {
+    let cpath = path_to_cstring(path.as_ref())?;
+    let mut buf = [0u8; nr::XATTR_CAPS_SZ_3];
+    let r = unsafe {
+        libc::getxattr(
+            cpath.as_ptr(),
+            nr::XATTR_NAME_CAPS.as_ptr().cast(),
+            buf.as_mut_ptr().cast(),
+            buf.len(),
+        )
+    };
+    xattr_result(r, &buf, &path.as_ref().display().to_string())
+}
+
+/// Read capabilities of an open file.
+///
+/// If the file has no capabilities attached, this returns `Ok(None)`.
+pub fn read_fd<F: AsRawFd>(file: &F) -> Result<Option<FileCaps>, CapsError> {
+    let fd = file.as_raw_fd();
+    let mut buf = [0u8; nr::XATTR_CAPS_SZ_3];
+    let r = unsafe {
+        libc::fgetxattr(
+            fd,
+            nr::XATTR_NAME_CAPS.as_ptr().cast(),
+            buf.as_mut_ptr().cast(),
+            buf.len(),
+        )
+    };
+    xattr_result(r, &buf, &format!("fd {}", fd))
+}
+
+/// Set capabilities of the file at `path`.
+///
+/// This replaces any capabilities already attached to the file, using the
+/// `VFS_CAP_REVISION_2` format, or `VFS_CAP_REVISION_3` if `value.rootid`
+/// is set. It requires `CAP_SETFCAP` and a filesystem supporting extended
+/// attributes. Symbolic links are not followed and are rejected instead.
+pub fn set<P: AsRef<Path>>(path: P, value: &FileCaps) -> Result<(), CapsError> {
+    let path = path.as_ref();
+    let cpath = no_symlink_cstring(path)?;
+    let buf = value.to_bytes();
+    let r = unsafe {
+        libc::lsetxattr(
+            cpath.as_ptr(),
+            nr::XATTR_NAME_CAPS.as_ptr().cast(),
+            buf.as_ptr().cast(),
+            buf.len(),
+            0,
+        )
+    };
+    match r {
+        0 => Ok(()),
+        _ => Err(xattr_error("setxattr", path, Error::last_os_error())),
+    }
+}
+
+/// Remove all capabilities from the file at `path`.
+///
+/// Removing capabilities from a file without any is not an error.
+/// It requires `CAP_SETFCAP` and a filesystem supporting extended
+/// attributes. Symbolic links are not followed and are rejected instead.
+pub fn remove<P: AsRef<Path>>(path: P) -> Result<(), CapsError> {
+    let path = path.as_ref();
+    let cpath = no_symlink_cstring(path)?;
+    let r = unsafe { libc::lremovexattr(cpath.as_ptr(), nr::XATTR_NAME_CAPS.as_ptr().cast()) };
+    if r == 0 {
+        return Ok(());
+    }
+    let err = Error::last_os_error();
+    match err.raw_os_error() {
+        Some(libc::ENODATA) => Ok(()),
+        _ => Err(xattr_error("removexattr", path, err)),
+    }
+}
+
+/// Convert `path` for use with `l*xattr` functions, rejecting symbolic links.
+fn no_symlink_cstring(path: &Path) -> Result<CString, CapsError> {
+    let meta = std::fs::symlink_metadata(path)
+        .map_err(|e| CapsError::io(format_args!("failed to stat '{}'", path.display()), e))?;
+    if meta.file_type().is_symlink() {
+        return Err(CapsError::new(
+            ErrorKind::SymbolicLink,
+            format!(
+                "'{}' is a symbolic link, file capabilities cannot be attached to it",
+                path.display()
+            ),
+        ));
+    }
+    path_to_cstring(path)
+}
+
+fn xattr_error(op: &str, path: &Path, err: Error) -> CapsError {
+    let hint = match err.raw_os_error() {
+        Some(libc::EPERM) => " (CAP_SETFCAP required)",
+        Some(libc::ENOTSUP) => " (filesystem lacks extended attributes support)",
+        _ => "",
+    };
+    let mut res = CapsError::io(format_args!("{} failure on {}", op, path.display()), err);
+    res.message.push_str(hint);
+    res
+}
+
+fn path_to_cstring(path: &Path) -> Result<CString, CapsError> {
+    CString::new(path.as_os_str().as_bytes()).map_err(|_| {
+        CapsError::new(
+            ErrorKind::InvalidInput,
+            format!("invalid path '{}': contains NUL byte", path.display()),
+        )
+    })
+}
+
+fn xattr_result(r: isize, buf: &[u8], target: &str) -> Result<Option<FileCaps>, CapsError> {
+    if r < 0 {
+        let err = Error::last_os_error();
+        if err.raw_os_error() == Some(libc::ENODATA) {
+            return Ok(None);
+        }
+        return Err(CapsError::io(
+            format_args!("getxattr failure on {}", target),
+            err,
+        ));
+    }
+    FileCaps::from_bytes(&buf[..r as usize]).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Capability;
+
+    fn caps(list: &[Capability]) -> CapsBitSet {
+        list.iter().collect()
+    }
+
+    // Test vectors, as written by `setcap(8)`.
+    const VECTORS: &[(&str, &[u8])] = &[
+        (
+            "cap_net_raw,cap_bpf+ep",
+            &[
+                0x01, 0x00, 0x00, 0x02, 0x00, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            ],
+        ),
+        (
+            "cap_chown=i",
+            &[
+                0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            ],
+        ),
+        (
+            "cap_setuid,cap_checkpoint_restore=eip",
+            &[
+                0x01, 0x00, 0x00, 0x02, 0x80, 0x00, 0x00, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x01,
+                0x00, 0x00, 0x00, 0x01, 0x00, 0x00,
+            ],
+        ),
+        (
+            "-n 1000 cap_net_bind_service+ep",
+            &[
+                0x01, 0x00, 0x00, 0x03, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xe8, 0x03, 0x00, 0x00,
+            ],
+        ),
+    ];
+
+    fn expected(name: &str) -> FileCaps {
+        match name {
+            "cap_net_raw,cap_bpf+ep" => FileCaps {
+                permitted: caps(&[Capability::CAP_NET_RAW, Capability::CAP_BPF]),
+                inheritable: caps(&[]),
+                effective: true,
+                rootid: None,
+            },
+            "cap_chown=i" => FileCaps {
+                permitted: caps(&[]),
+                inheritable: caps(&[Capability::CAP_CHOWN]),
+                effective: false,
+                rootid: None,
+            },
+            "cap_setuid,cap_checkpoint_restore=eip" => {
+                let set = caps(&[Capability::CAP_SETUID, Capability::CAP_CHECKPOINT_RESTORE]);
+                FileCaps {
+                    permitted: set,
+                    inheritable: set,
+                    effective: true,
+                    rootid: None,
+                }
+            }
+            "-n 1000 cap_net_bind_service+ep" => FileCaps {
+                permitted: caps(&[Capability::CAP_NET_BIND_SERVICE]),
+                inheritable: caps(&[]),
+                effective: true,
+                rootid: Some(1000),
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_vectors() {
+        for (name, bytes) in VECTORS {
+            let fc = FileCaps::from_bytes(bytes).unwrap();
+            assert_eq!(fc, expected(name), "{}", name);
+            assert_eq!(&fc.to_bytes(), bytes, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_decode_v1() {
+        let buf = [
+            0x01, 0x00, 0x00, 0x01, 0x00, 0x04, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
+        ];
+        let fc = FileCaps::from_bytes(&buf).unwrap();
+        assert_eq!(fc.permitted, caps(&[Capability::CAP_NET_BIND_SERVICE]));
+        assert_eq!(fc.inheritable, caps(&[Capability::CAP_CHOWN]));
+        assert!(fc.effective);
+        assert_eq!(fc.rootid, None);
+        // Re-encoding upgrades to revision 2.
+        assert_eq!(fc.to_bytes().len(), nr::XATTR_CAPS_SZ_2);
+    }
+
+    #[test]
+    fn test_unknown_caps() {
+        // `setcap cap_41,cap_chown=p` on a kernel newer than this library.
+        let buf = [
+            0x00, 0x00, 0x00, 0x02, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let fc = FileCaps::from_bytes(&buf).unwrap();
+        assert_eq!(fc.permitted.indices().collect::<Vec<_>>(), vec![0, 41]);
+        assert!(fc.inheritable.is_empty());
+        assert_eq!(fc.to_bytes(), buf);
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        // Too short.
+        assert!(FileCaps::from_bytes(&[]).is_err());
+        assert!(FileCaps::from_bytes(&[0x00, 0x00, 0x00]).is_err());
+        // Unknown revision.
+        assert!(FileCaps::from_bytes(&[0x00, 0x00, 0x00, 0x04]).is_err());
+        assert!(FileCaps::from_bytes(&[0x00; 20]).is_err());
+        // Wrong length for revision.
+        for (_, bytes) in VECTORS {
+            assert!(FileCaps::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+            let mut longer = bytes.to_vec();
+            longer.push(0x00);
+            assert!(FileCaps::from_bytes(&longer).is_err());
+        }
+        let mut v2_as_v3 = VECTORS[0].1.to_vec();
+        v2_as_v3[3] = 0x03;
+        assert!(FileCaps::from_bytes(&v2_as_v3).is_err());
+        // Unknown flags.
+        let mut flags = VECTORS[0].1.to_vec();
+        flags[0] = 0x02;
+        assert!(FileCaps::from_bytes(&flags).is_err());
+    }
+
+    #[test]
+    fn test_xattr_error() {
+        let path = Path::new("/some/file");
+        let err = xattr_error("setxattr", path, Error::from_raw_os_error(libc::ENOTSUP));
+        assert_eq!(err.kind(), ErrorKind::NotSupported);
+        assert!(err
+            .to_string()
+            .contains("lacks extended attributes support"));
+        let err = xattr_error("setxattr", path, Error::from_raw_os_error(libc::EPERM));
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        assert!(err.to_string().contains("CAP_SETFCAP required"));
+    }
+}
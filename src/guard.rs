@@ -0,0 +1,104 @@
+//! Implementation of scoped effective capabilities.
+
+use crate::errors::{CapsError, ErrorKind};
+use crate::{base, CapSet, Capability, CapsBitSet};
+use std::marker::PhantomData;
+
+/// Guard for capabilities temporarily raised in the Effective set.
+///
+/// This is returned by [`elevate`], and restores the previous Effective
+/// set when dropped. Capabilities are per-thread, so this guard is not
+/// `Send`: it can only be dropped on the thread whose set it changed.
+///
+/// Errors while restoring on drop are ignored; use [`restore`] to
+/// handle them instead.
+///
+/// [`elevate`]: fn.elevate.html
+/// [`restore`]: struct.EffectiveGuard.html#method.restore
+#[derive(Debug)]
+#[must_use = "capabilities are dropped again as soon as the guard is dropped"]
+pub struct EffectiveGuard {
+    previous: CapsBitSet,
+    restored: bool,
+    // Raw pointers are neither `Send` nor `Sync`.
+    _thread: PhantomData<*const ()>,
+}
+
+impl EffectiveGuard {
+    /// Return the Effective set as it was before elevation.
+    pub fn previous(&self) -> CapsBitSet {
+        self.previous
+    }
+
+    /// Restore the previous Effective set, reporting any error.
+    pub fn restore(mut self) -> Result<(), CapsError> {
+        self.restored = true;
+        restore_effective(self.previous)
+    }
+}
+
+impl Drop for EffectiveGuard {
+    fn drop(&mut self) {
+        if !self.restored {
+            let _ = restore_effective(self.previous);
+        }
+    }
+}
+
+/// Outcome of [`with_effective`].
+///
+/// [`with_effective`]: fn.with_effective.html
+#[derive(Debug)]
+pub struct EffectiveOutcome<T> {
+    /// Value returned by the closure.
+    pub value: T,
+    /// Result of restoring the previous Effective set afterwards.
+    pub restore: Result<(), CapsError>,
+}
+
+pub fn with_effective<T, F: FnOnce() -> T>(
+    caps: &[Capability],
+    f: F,
+) -> Result<EffectiveOutcome<T>, CapsError> {
+    let permitted = crate::read(None, CapSet::Permitted)?;
+    if let Some(c) = caps.iter().find(|c| !permitted.contains(c)) {
+        return Err(CapsError::new(
+            ErrorKind::PermissionDenied,
+            format!("{} is not in permitted set", c),
+        )
+        .with_cap(*c)
+        .with_set(CapSet::Permitted));
+    }
+    let guard = elevate(caps)?;
+    // On panic, the guard still restores the Effective set while unwinding.
+    let value = f();
+    Ok(EffectiveOutcome {
+        value,
+        restore: guard.restore(),
+    })
+}
+
+pub fn elevate(caps: &[Capability]) -> Result<EffectiveGuard, CapsError> {
+    let mut state = base::read_all(0)?;
+    let previous = state.effective;
+    state.effective |= caps.iter().collect();
+    if state.effective != previous {
+        base::set_all(0, &state)?;
+    }
+    Ok(EffectiveGuard {
+        previous,
+        restored: false,
+        _thread: PhantomData,
+    })
+}
+
+fn restore_effective(previous: CapsBitSet) -> Result<(), CapsError> {
+    let mut state = base::read_all(0)?;
+    // Capabilities dropped from Permitted meanwhile cannot come back.
+    let effective = previous & state.permitted;
+    if state.effective != effective {
+        state.effective = effective;
+        base::set_all(0, &state)?;
+    }
+    Ok(())
+}
@@ -14,11 +14,11 @@ fn manipulate_caps() -> ExResult<()> {
     if caps::has_cap(None, CapSet::Permitted, Capability::CAP_SYS_NICE)? {
         caps::drop(None, CapSet::Effective, Capability::CAP_SYS_NICE)?;
         let effective = caps::read(None, CapSet::Effective)?;
-        assert_eq!(effective.contains(&Capability::CAP_SYS_NICE), false);
+        assert!(!effective.contains(&Capability::CAP_SYS_NICE));
 
         caps::clear(None, CapSet::Effective)?;
         let cleared = caps::read(None, CapSet::Effective)?;
-        assert_eq!(cleared.is_empty(), true);
+        assert!(cleared.is_empty());
     };
 
     Ok(())
@@ -26,20 +26,37 @@ fn manipulate_caps() -> ExResult<()> {
 ```
 */
 
+pub mod command;
 pub mod errors;
+pub mod file;
+pub mod procfs;
+pub mod raw;
 pub mod runtime;
 pub mod securebits;
+pub mod text;
 
 // Implementation of Bounding set.
 mod ambient;
 // Implementation of POSIX sets.
 mod base;
+// Implementation of compact capabilities sets.
+mod bitset;
 // Implementation of Bounding set.
 mod bounding;
+// Implementation of scoped effective capabilities.
+mod guard;
+// Implementation of privileges lockdown.
+mod lockdown;
 // All kernel-related constants.
 mod nr;
+// Implementation of thread capabilities state.
+mod state;
 
-use crate::errors::CapsError;
+pub use crate::bitset::{CapsBitSet, CapsBitSetIndices, CapsBitSetIter, UnknownBits};
+use crate::errors::{CapsError, ErrorKind};
+pub use crate::guard::{EffectiveGuard, EffectiveOutcome};
+pub use crate::lockdown::LockdownReport;
+pub use crate::state::{BaseState, CapState};
 use std::iter::FromIterator;
 
 /// Linux capabilities sets.
@@ -243,7 +260,10 @@ impl std::str::FromStr for Capability {
             "CAP_PERFMON" => Ok(Capability::CAP_PERFMON),
             "CAP_BPF" => Ok(Capability::CAP_BPF),
             "CAP_CHECKPOINT_RESTORE" => Ok(Capability::CAP_CHECKPOINT_RESTORE),
-            _ => Err(format!("invalid capability: {}", s).into()),
+            _ => Err(CapsError::new(
+                ErrorKind::InvalidCapability,
+                format!("invalid capability: {}", s),
+            )),
         }
     }
 }
@@ -260,6 +280,14 @@ impl Capability {
     pub fn index(&self) -> u8 {
         *self as u8
     }
+
+    /// Returns the capability with kernel-defined value `index`.
+    ///
+    /// This returns `None` for capabilities unknown to this library,
+    /// which can still be handled by index through `CapsBitSet`.
+    pub fn from_index(index: u8) -> Option<Self> {
+        ALL_CAPS.get(usize::from(index)).copied()
+    }
 }
 
 /// An `HashSet` specialized on `Capability`.
@@ -269,30 +297,64 @@ pub type CapsHashSet = std::collections::HashSet<Capability>;
 ///
 /// Check if set `cset` for thread `tid` contains capability `cap`.
 /// If `tid` is `None`, this operates on current thread (tid=0).
-/// It cannot check Ambient or Bounding capabilities of other processes.
+/// Ambient and Bounding sets of other threads are read from procfs.
 pub fn has_cap(tid: Option<i32>, cset: CapSet, cap: Capability) -> Result<bool, CapsError> {
-    let t = tid.unwrap_or(0);
-    match cset {
+    let t = current_or(tid);
+    let res = match cset {
         CapSet::Ambient if t == 0 => ambient::has_cap(cap),
         CapSet::Bounding if t == 0 => bounding::has_cap(cap),
+        CapSet::Ambient | CapSet::Bounding => {
+            procfs::read_thread(t).map(|st| st.get(cset).contains(cap))
+        }
         CapSet::Effective | CapSet::Inheritable | CapSet::Permitted => base::has_cap(t, cset, cap),
-        _ => Err("operation not supported".into()),
-    }
+    };
+    res.map_err(|e| e.with_cap(cap).with_set(cset).with_tid(t))
 }
 
 /// Return all capabilities in a set for a thread.
 ///
 /// Return current content of set `cset` for thread `tid`.
 /// If `tid` is `None`, this operates on current thread (tid=0).
-/// It cannot read Ambient or Bounding capabilities of other processes.
+/// Ambient and Bounding sets of other threads are read from procfs.
 pub fn read(tid: Option<i32>, cset: CapSet) -> Result<CapsHashSet, CapsError> {
-    let t = tid.unwrap_or(0);
-    match cset {
+    let t = current_or(tid);
+    let res = match cset {
         CapSet::Ambient if t == 0 => ambient::read(),
         CapSet::Bounding if t == 0 => bounding::read(),
+        CapSet::Ambient | CapSet::Bounding => procfs::read_thread(t).map(|st| st.get(cset).into()),
         CapSet::Effective | CapSet::Inheritable | CapSet::Permitted => base::read(t, cset),
-        _ => Err("operation not supported".into()),
-    }
+    };
+    res.map_err(|e| e.with_set(cset).with_tid(t))
+}
+
+/// Return all capabilities in a set for a thread, as a `CapsBitSet`.
+///
+/// This behaves like [`read`], but it does not allocate on success,
+/// except for Ambient and Bounding sets of other threads, which are read
+/// from procfs.
+///
+/// [`read`]: fn.read.html
+pub fn read_bits(tid: Option<i32>, cset: CapSet) -> Result<CapsBitSet, CapsError> {
+    let t = current_or(tid);
+    let res = match cset {
+        CapSet::Ambient if t == 0 => ambient::read_bits(),
+        CapSet::Bounding if t == 0 => bounding::read_bits(),
+        CapSet::Ambient | CapSet::Bounding => procfs::read_thread(t).map(|st| st.get(cset)),
+        CapSet::Effective | CapSet::Inheritable | CapSet::Permitted => base::read_bits(t, cset),
+    };
+    res.map_err(|e| e.with_set(cset).with_tid(t))
+}
+
+/// Return Effective, Permitted and Inheritable sets for a thread.
+///
+/// All three sets are read at once with a single `capget(2)` call.
+/// If `tid` is `None`, this operates on current thread (tid=0).
+/// The returned snapshot can be modified and applied back with [`set_base`].
+///
+/// [`set_base`]: fn.set_base.html
+pub fn read_base(tid: Option<i32>) -> Result<BaseState, CapsError> {
+    let t = current_or(tid);
+    base::read_all(t).map_err(|e| e.with_tid(t))
 }
 
 /// Set a capability set for a thread to a new value.
@@ -302,12 +364,42 @@ pub fn read(tid: Option<i32>, cset: CapSet) -> Result<CapsHashSet, CapsError> {
 /// It cannot manipulate Ambient set of other processes.
 /// Capabilities cannot be set in Bounding set.
 pub fn set(tid: Option<i32>, cset: CapSet, value: &CapsHashSet) -> Result<(), CapsError> {
-    let t = tid.unwrap_or(0);
-    match cset {
+    let t = current_or(tid);
+    let res = match cset {
         CapSet::Ambient if t == 0 => ambient::set(value),
         CapSet::Effective | CapSet::Inheritable | CapSet::Permitted => base::set(t, cset, value),
-        _ => Err("operation not supported".into()),
-    }
+        _ => Err(unsupported()),
+    };
+    res.map_err(|e| e.with_set(cset).with_tid(t))
+}
+
+/// Set a capability set for a thread to a new value, from a `CapsBitSet`.
+///
+/// This behaves like [`set`], but it does not allocate on success.
+/// Like [`set`], it only manipulates the Ambient set of the current thread.
+///
+/// [`set`]: fn.set.html
+pub fn set_bits(tid: Option<i32>, cset: CapSet, value: CapsBitSet) -> Result<(), CapsError> {
+    let t = current_or(tid);
+    let res = match cset {
+        CapSet::Ambient if t == 0 => ambient::set_bits(value),
+        CapSet::Effective | CapSet::Inheritable | CapSet::Permitted => {
+            base::set_bits(t, cset, value)
+        }
+        _ => Err(unsupported()),
+    };
+    res.map_err(|e| e.with_set(cset).with_tid(t))
+}
+
+/// Set Effective, Permitted and Inheritable sets for a thread at once.
+///
+/// All three sets are replaced by the content of `value` with a single
+/// `capset(2)` call, so that no intermediate state is ever applied.
+/// If `tid` is `None`, this operates on current thread (tid=0).
+/// If the kernel rejects the new state, the returned error reports which
+/// `capset(2)` rule was violated.
+pub fn set_base(tid: Option<i32>, value: &BaseState) -> Result<(), CapsError> {
+    base::set_all(current_or(tid), value)
 }
 
 /// Clear all capabilities in a set for a thread.
@@ -316,13 +408,14 @@ pub fn set(tid: Option<i32>, cset: CapSet, value: &CapsHashSet) -> Result<(), Ca
 /// If `tid` is `None`, this operates on current thread (tid=0).
 /// It cannot manipulate Ambient or Bounding set of other processes.
 pub fn clear(tid: Option<i32>, cset: CapSet) -> Result<(), CapsError> {
-    let t = tid.unwrap_or(0);
-    match cset {
+    let t = current_or(tid);
+    let res = match cset {
         CapSet::Ambient if t == 0 => ambient::clear(),
         CapSet::Bounding if t == 0 => bounding::clear(),
         CapSet::Effective | CapSet::Permitted | CapSet::Inheritable => base::clear(t, cset),
-        _ => Err("operation not supported".into()),
-    }
+        _ => Err(unsupported()),
+    };
+    res.map_err(|e| e.with_set(cset).with_tid(t))
 }
 
 /// Raise a single capability in a set for a thread.
@@ -332,12 +425,13 @@ pub fn clear(tid: Option<i32>, cset: CapSet) -> Result<(), CapsError> {
 /// It cannot manipulate Ambient set of other processes.
 /// Capabilities cannot be raised in Bounding set.
 pub fn raise(tid: Option<i32>, cset: CapSet, cap: Capability) -> Result<(), CapsError> {
-    let t = tid.unwrap_or(0);
-    match cset {
+    let t = current_or(tid);
+    let res = match cset {
         CapSet::Ambient if t == 0 => ambient::raise(cap),
         CapSet::Effective | CapSet::Permitted | CapSet::Inheritable => base::raise(t, cset, cap),
-        _ => Err("operation not supported".into()),
-    }
+        _ => Err(unsupported()),
+    };
+    res.map_err(|e| e.with_cap(cap).with_set(cset).with_tid(t))
 }
 
 /// Drop a single capability from a set for a thread.
@@ -346,63 +440,154 @@ pub fn raise(tid: Option<i32>, cset: CapSet, cap: Capability) -> Result<(), Caps
 /// If `tid` is `None`, this operates on current thread (tid=0).
 /// It cannot manipulate Ambient and Bounding sets of other processes.
 pub fn drop(tid: Option<i32>, cset: CapSet, cap: Capability) -> Result<(), CapsError> {
-    let t = tid.unwrap_or(0);
-    match cset {
+    let t = current_or(tid);
+    let res = match cset {
         CapSet::Ambient if t == 0 => ambient::drop(cap),
         CapSet::Bounding if t == 0 => bounding::drop(cap),
         CapSet::Effective | CapSet::Permitted | CapSet::Inheritable => base::drop(t, cset, cap),
-        _ => Err("operation not supported".into()),
+        _ => Err(unsupported()),
+    };
+    res.map_err(|e| e.with_cap(cap).with_set(cset).with_tid(t))
+}
+
+/// Temporarily raise capabilities in the Effective set of the current thread.
+///
+/// All capabilities in `caps` must be in the Permitted set. They stay
+/// raised until the returned guard is dropped, which restores the
+/// previous Effective set, also on early returns and panics:
+///
+/// ```rust
+/// use caps::Capability;
+///
+/// if let Ok(_guard) = caps::elevate(&[Capability::CAP_SYS_NICE]) {
+///     // CAP_SYS_NICE is effective until the end of this scope.
+/// }
+/// ```
+pub fn elevate(caps: &[Capability]) -> Result<EffectiveGuard, CapsError> {
+    guard::elevate(caps)
+}
+
+/// Run `f` with capabilities temporarily raised in the Effective set.
+///
+/// This fails early, without raising anything, if any capability in
+/// `caps` is not in the Permitted set. Otherwise, after `f` returns (or
+/// panics), the previous Effective set is restored. The returned outcome
+/// holds both the value returned by `f` and the result of restoring:
+///
+/// ```rust
+/// use caps::Capability;
+///
+/// let outcome = caps::with_effective(&[Capability::CAP_SYS_NICE], || {
+///     unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, -5) }
+/// });
+/// if let Ok(out) = outcome {
+///     println!("setpriority returned {}", out.value);
+///     out.restore.expect("failed to restore effective set");
+/// }
+/// ```
+pub fn with_effective<T, F: FnOnce() -> T>(
+    caps: &[Capability],
+    f: F,
+) -> Result<EffectiveOutcome<T>, CapsError> {
+    guard::with_effective(caps, f)
+}
+
+/// Reduce all capabilities sets of the current thread to `keep`.
+///
+/// Every capability not in `keep` is removed from the Effective,
+/// Permitted, Inheritable, Ambient and Bounding sets, in an order the
+/// kernel accepts. Capabilities in `keep` are left where they are, and
+/// are not added to any set.
+///
+/// Reducing the Bounding set requires `CAP_SETPCAP` in the Permitted set;
+/// if it is not in the Effective set, it is temporarily raised there.
+/// The resulting state is checked, and returned on success.
+pub fn restrict_to(keep: &CapsHashSet) -> Result<CapState, CapsError> {
+    lockdown::restrict_to(keep)
+}
+
+/// Lock down privileges of the current thread.
+///
+/// This irreversibly prevents the current thread (and its future children)
+/// from regaining privileges, even by executing setuid or file-capabilities
+/// binaries. In order, it:
+///  * clears the Ambient set, if supported,
+///  * drops from the Bounding set all capabilities not in `keep`,
+///    including those unknown to this library,
+///  * sets and locks `SECURE_NOROOT`, `SECURE_NO_SETUID_FIXUP` and
+///    `SECURE_NO_CAP_AMBIENT_RAISE` securebits (the latter if Ambient is supported),
+///  * sets the "no new privileges" flag.
+///
+/// Effective, Permitted and Inheritable sets are left untouched.
+/// This requires `CAP_SETPCAP` in the Permitted set; if it is not in the
+/// Effective set, it is temporarily raised there.
+/// The returned report lists what was actually changed.
+pub fn lockdown(keep: &CapsHashSet) -> Result<LockdownReport, CapsError> {
+    lockdown::lockdown(keep)
+}
+
+fn unsupported() -> CapsError {
+    CapsError::new(ErrorKind::NotSupported, "operation not supported")
+}
+
+/// Resolve `tid`, mapping the calling thread itself to 0.
+fn current_or(tid: Option<i32>) -> i32 {
+    match tid {
+        Some(t) if t == unsafe { libc::syscall(libc::SYS_gettid) } as i32 => 0,
+        t => t.unwrap_or(0),
     }
 }
 
 /// Return the set of all capabilities supported by this library.
 pub fn all() -> CapsHashSet {
-    let slice = vec![
-        Capability::CAP_CHOWN,
-        Capability::CAP_DAC_OVERRIDE,
-        Capability::CAP_DAC_READ_SEARCH,
-        Capability::CAP_FOWNER,
-        Capability::CAP_FSETID,
-        Capability::CAP_KILL,
-        Capability::CAP_SETGID,
-        Capability::CAP_SETUID,
-        Capability::CAP_SETPCAP,
-        Capability::CAP_LINUX_IMMUTABLE,
-        Capability::CAP_NET_BIND_SERVICE,
-        Capability::CAP_NET_BROADCAST,
-        Capability::CAP_NET_ADMIN,
-        Capability::CAP_NET_RAW,
-        Capability::CAP_IPC_LOCK,
-        Capability::CAP_IPC_OWNER,
-        Capability::CAP_SYS_MODULE,
-        Capability::CAP_SYS_RAWIO,
-        Capability::CAP_SYS_CHROOT,
-        Capability::CAP_SYS_PTRACE,
-        Capability::CAP_SYS_PACCT,
-        Capability::CAP_SYS_ADMIN,
-        Capability::CAP_SYS_BOOT,
-        Capability::CAP_SYS_NICE,
-        Capability::CAP_SYS_RESOURCE,
-        Capability::CAP_SYS_TIME,
-        Capability::CAP_SYS_TTY_CONFIG,
-        Capability::CAP_MKNOD,
-        Capability::CAP_LEASE,
-        Capability::CAP_AUDIT_WRITE,
-        Capability::CAP_AUDIT_CONTROL,
-        Capability::CAP_SETFCAP,
-        Capability::CAP_MAC_OVERRIDE,
-        Capability::CAP_MAC_ADMIN,
-        Capability::CAP_SYSLOG,
-        Capability::CAP_WAKE_ALARM,
-        Capability::CAP_BLOCK_SUSPEND,
-        Capability::CAP_AUDIT_READ,
-        Capability::CAP_PERFMON,
-        Capability::CAP_BPF,
-        Capability::CAP_CHECKPOINT_RESTORE,
-    ];
-    CapsHashSet::from_iter(slice)
+    CapsHashSet::from_iter(ALL_CAPS)
 }
 
+/// All capabilities supported by this library, ordered by index.
+pub(crate) const ALL_CAPS: [Capability; 41] = [
+    Capability::CAP_CHOWN,
+    Capability::CAP_DAC_OVERRIDE,
+    Capability::CAP_DAC_READ_SEARCH,
+    Capability::CAP_FOWNER,
+    Capability::CAP_FSETID,
+    Capability::CAP_KILL,
+    Capability::CAP_SETGID,
+    Capability::CAP_SETUID,
+    Capability::CAP_SETPCAP,
+    Capability::CAP_LINUX_IMMUTABLE,
+    Capability::CAP_NET_BIND_SERVICE,
+    Capability::CAP_NET_BROADCAST,
+    Capability::CAP_NET_ADMIN,
+    Capability::CAP_NET_RAW,
+    Capability::CAP_IPC_LOCK,
+    Capability::CAP_IPC_OWNER,
+    Capability::CAP_SYS_MODULE,
+    Capability::CAP_SYS_RAWIO,
+    Capability::CAP_SYS_CHROOT,
+    Capability::CAP_SYS_PTRACE,
+    Capability::CAP_SYS_PACCT,
+    Capability::CAP_SYS_ADMIN,
+    Capability::CAP_SYS_BOOT,
+    Capability::CAP_SYS_NICE,
+    Capability::CAP_SYS_RESOURCE,
+    Capability::CAP_SYS_TIME,
+    Capability::CAP_SYS_TTY_CONFIG,
+    Capability::CAP_MKNOD,
+    Capability::CAP_LEASE,
+    Capability::CAP_AUDIT_WRITE,
+    Capability::CAP_AUDIT_CONTROL,
+    Capability::CAP_SETFCAP,
+    Capability::CAP_MAC_OVERRIDE,
+    Capability::CAP_MAC_ADMIN,
+    Capability::CAP_SYSLOG,
+    Capability::CAP_WAKE_ALARM,
+    Capability::CAP_BLOCK_SUSPEND,
+    Capability::CAP_AUDIT_READ,
+    Capability::CAP_PERFMON,
+    Capability::CAP_BPF,
+    Capability::CAP_CHECKPOINT_RESTORE,
+];
+
 /// Convert an informal capability name into a canonical form.
 ///
 /// This converts the input string to uppercase and ensures that it starts with
@@ -425,10 +610,19 @@ mod tests {
     use super::*;
     use std::str::FromStr;
 
+    #[test]
+    fn test_from_index() {
+        for c in ALL_CAPS.iter() {
+            assert_eq!(Capability::from_index(c.index()), Some(*c));
+        }
+        assert_eq!(Capability::from_index(41), None);
+        assert_eq!(Capability::from_index(255), None);
+    }
+
     #[test]
     fn test_all_roundtrip() {
         let all = all();
-        assert!(all.len() > 0);
+        assert!(!all.is_empty());
         for c in all {
             let name = c.to_string();
             let parsed: Capability = name.parse().unwrap();
@@ -463,7 +657,7 @@ mod tests {
         // Serialization
         {
             let p1 = Capability::from_str(input).unwrap();
-            let ser = serde_json::to_value(&p1).unwrap();
+            let ser = serde_json::to_value(p1).unwrap();
             let json_str = ser.as_str().unwrap();
             assert_eq!(json_str, input);
             let deser: Capability = serde_json::from_value(ser).unwrap();
@@ -473,7 +667,7 @@ mod tests {
         {
             let json_input = format!(r#""{}""#, input);
             let deser: Capability = serde_json::from_str(&json_input).unwrap();
-            let ser = serde_json::to_value(&deser).unwrap();
+            let ser = serde_json::to_value(deser).unwrap();
             let json_str = ser.as_str().unwrap();
             assert_eq!(json_str, input);
         }
@@ -0,0 +1,162 @@
+//! Implementation of privileges lockdown.
+
+use crate::errors::{CapsError, ErrorKind};
+use crate::runtime;
+use crate::securebits::{self, SecureBits};
+use crate::CapsHashSet;
+use crate::{ambient, base, bounding, text, BaseState, CapSet, CapState, Capability, CapsBitSet};
+
+/// Changes applied by [`lockdown`].
+///
+/// [`lockdown`]: fn.lockdown.html
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct LockdownReport {
+    /// Capabilities dropped from the Bounding set.
+    pub bounding_dropped: CapsBitSet,
+    /// Capabilities cleared from the Ambient set.
+    pub ambient_cleared: CapsBitSet,
+    /// Securebits flags which were not already set.
+    pub securebits_set: SecureBits,
+    /// Whether the "no new privileges" flag was not already set.
+    pub no_new_privs_set: bool,
+}
+
+pub fn lockdown(keep: &CapsHashSet) -> Result<LockdownReport, CapsError> {
+    // Dropping from the Bounding set and setting securebits requires
+    // CAP_SETPCAP, so temporarily raise it if only permitted.
+    let orig = base::read_all(0)?;
+    let setpcap = Capability::CAP_SETPCAP;
+    let raised = !orig.effective.contains(setpcap);
+    if raised {
+        if !orig.permitted.contains(setpcap) {
+            return Err(CapsError::new(
+                ErrorKind::PermissionDenied,
+                "lockdown requires CAP_SETPCAP in permitted set",
+            )
+            .with_cap(setpcap)
+            .with_set(CapSet::Permitted));
+        }
+        base::raise(0, CapSet::Effective, setpcap)?;
+    }
+    let report = apply(CapsBitSet::from(keep));
+    if raised {
+        // Best effort, the outcome of `apply` matters most.
+        let _ = base::drop(0, CapSet::Effective, setpcap);
+    }
+    report
+}
+
+fn apply(keep: CapsBitSet) -> Result<LockdownReport, CapsError> {
+    // Clear Ambient first, so that nothing can be inherited from it.
+    let has_ambient = runtime::has_ambient();
+    let mut report = LockdownReport::default();
+    if has_ambient {
+        report.ambient_cleared = ambient::read_bits()?;
+        ambient::clear()?;
+    }
+
+    // Reduce the Bounding set, which limits what any `execve` can grant.
+    // Unknown capabilities are never kept, as their effects cannot be vetted.
+    for index in (bounding::read_bits()? - keep).indices() {
+        bounding::drop_index(index)?;
+        report.bounding_dropped.insert_index(index);
+    }
+
+    // Disable and lock root special handling and ambient raising.
+    let mut flags = SecureBits::NOROOT | SecureBits::NO_SETUID_FIXUP;
+    if has_ambient {
+        flags = flags | SecureBits::NO_CAP_AMBIENT_RAISE;
+    }
+    let wanted = flags | flags.locks();
+    let current = securebits::read()?;
+    securebits::set(current | wanted)?;
+    report.securebits_set = wanted - current;
+
+    // Finally forbid gaining privileges through setuid or file capabilities.
+    report.no_new_privs_set = !securebits::get_no_new_privs()?;
+    securebits::set_no_new_privs()?;
+
+    Ok(report)
+}
+
+pub fn restrict_to(keep: &CapsHashSet) -> Result<CapState, CapsError> {
+    let keep = CapsBitSet::from(keep);
+    let orig = CapState::current()?;
+
+    // Lower Ambient first, as it must stay within Permitted and Inheritable.
+    // Kept capabilities are left alone, as raising them again would fail
+    // under SECURE_NO_CAP_AMBIENT_RAISE.
+    if runtime::has_ambient() {
+        (orig.ambient - keep)
+            .indices()
+            .try_for_each(ambient::drop_index)?;
+    }
+
+    // Reduce Bounding, which requires CAP_SETPCAP in Effective.
+    let extra_bounding = orig.bounding - keep;
+    let setpcap = Capability::CAP_SETPCAP;
+    let mut raised = false;
+    if !extra_bounding.is_empty() {
+        raised = !orig.effective.contains(setpcap);
+        if raised {
+            if !orig.permitted.contains(setpcap) {
+                return Err(CapsError::new(
+                    ErrorKind::PermissionDenied,
+                    "reducing bounding set requires CAP_SETPCAP in permitted set",
+                )
+                .with_cap(setpcap)
+                .with_set(CapSet::Permitted));
+            }
+            base::raise(0, CapSet::Effective, setpcap)?;
+        }
+        if let Err(e) = extra_bounding.indices().try_for_each(bounding::drop_index) {
+            if raised {
+                let _ = base::drop(0, CapSet::Effective, setpcap);
+            }
+            return Err(e);
+        }
+    }
+
+    // Finally lower Effective, Permitted and Inheritable at once. This also
+    // drops CAP_SETPCAP if it was only raised above.
+    let target = BaseState {
+        effective: orig.effective & keep,
+        permitted: orig.permitted & keep,
+        inheritable: orig.inheritable & keep,
+    };
+    if let Err(e) = base::set_all(0, &target) {
+        if raised {
+            let _ = base::drop(0, CapSet::Effective, setpcap);
+        }
+        return Err(e);
+    }
+
+    // Check that exactly the kept capabilities are left in each set.
+    let current = CapState::current()?;
+    for cset in &[
+        CapSet::Effective,
+        CapSet::Permitted,
+        CapSet::Inheritable,
+        CapSet::Ambient,
+        CapSet::Bounding,
+    ] {
+        let (before, after) = (orig.get(*cset), current.get(*cset));
+        let lost = ((before & keep) - after).indices().next();
+        let remains = (after - keep).indices().next();
+        let (index, what) = match (lost, remains) {
+            (Some(index), _) => (index, "was lost from"),
+            (None, Some(index)) => (index, "remains in"),
+            (None, None) => continue,
+        };
+        let mut err = CapsError::new(
+            ErrorKind::Other,
+            format!("{} {} {:?} set", text::cap_name(index), what, cset),
+        )
+        .with_set(*cset);
+        if let Some(c) = Capability::from_index(index) {
+            err = err.with_cap(c);
+        }
+        return Err(err);
+    }
+    Ok(current)
+}
@@ -42,12 +42,44 @@ pub const CAP_PERFMON: u8 = 38;
 pub const CAP_BPF: u8 = 39;
 pub const CAP_CHECKPOINT_RESTORE: u8 = 40;
 
+pub const VFS_CAP_REVISION_MASK: u32 = 0xFF00_0000;
+pub const VFS_CAP_FLAGS_EFFECTIVE: u32 = 0x0000_0001;
+pub const VFS_CAP_REVISION_1: u32 = 0x0100_0000;
+pub const VFS_CAP_REVISION_2: u32 = 0x0200_0000;
+pub const VFS_CAP_REVISION_3: u32 = 0x0300_0000;
+pub const XATTR_CAPS_SZ_1: usize = 4 + 2 * 4;
+pub const XATTR_CAPS_SZ_2: usize = 4 + 2 * 2 * 4;
+pub const XATTR_CAPS_SZ_3: usize = 4 + 2 * 2 * 4 + 4;
+
+pub const LINUX_CAPABILITY_VERSION_1: u32 = 0x1998_0330;
+pub const LINUX_CAPABILITY_VERSION_2: u32 = 0x2007_1026;
+pub const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+/* from <linux/xattr.h> */
+
+pub const XATTR_NAME_CAPS: &[u8] = b"security.capability\0";
+
+/* from <linux/securebits.h> */
+
+pub const SECURE_NOROOT: u32 = 0;
+pub const SECURE_NOROOT_LOCKED: u32 = 1;
+pub const SECURE_NO_SETUID_FIXUP: u32 = 2;
+pub const SECURE_NO_SETUID_FIXUP_LOCKED: u32 = 3;
+pub const SECURE_KEEP_CAPS: u32 = 4;
+pub const SECURE_KEEP_CAPS_LOCKED: u32 = 5;
+pub const SECURE_NO_CAP_AMBIENT_RAISE: u32 = 6;
+pub const SECURE_NO_CAP_AMBIENT_RAISE_LOCKED: u32 = 7;
+
 /* from <sys/prctl.h> */
 
 pub const PR_GET_KEEPCAPS: i32 = 7;
 pub const PR_SET_KEEPCAPS: i32 = 8;
+pub const PR_GET_SECUREBITS: i32 = 27;
+pub const PR_SET_SECUREBITS: i32 = 28;
 pub const PR_CAPBSET_READ: i32 = 23;
 pub const PR_CAPBSET_DROP: i32 = 24;
+pub const PR_SET_NO_NEW_PRIVS: i32 = 38;
+pub const PR_GET_NO_NEW_PRIVS: i32 = 39;
 pub const PR_CAP_AMBIENT: i32 = 47;
 pub const PR_CAP_AMBIENT_IS_SET: i32 = 1;
 pub const PR_CAP_AMBIENT_RAISE: i32 = 2;
@@ -0,0 +1,259 @@
+/*!
+Read capabilities of any process from procfs.
+
+This module exposes methods to parse capabilities-related fields
+from `/proc/<pid>/status` and `/proc/<pid>/task/<tid>/status`.
+Unlike [`caps::read`], this allows inspecting all sets (including
+Ambient and Bounding ones) of other processes and threads.
+
+## Example
+
+```rust
+let status = caps::procfs::read_pid(1, None);
+if let Ok(st) = status {
+    println!("Bounding set of PID 1: {:?}", st.bounding);
+}
+```
+
+[`caps::read`]: ../fn.read.html
+*/
+
+use crate::errors::{CapsError, ErrorKind};
+use crate::{BaseState, CapSet, CapState, CapsBitSet, UnknownBits};
+use std::path::{Path, PathBuf};
+
+/// Seccomp mode of a thread.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub enum SeccompMode {
+    /// Seccomp is not in use (`SECCOMP_MODE_DISABLED`).
+    Disabled,
+    /// Strict mode (`SECCOMP_MODE_STRICT`).
+    Strict,
+    /// Filter mode (`SECCOMP_MODE_FILTER`).
+    Filter,
+}
+
+/// Capabilities state of a thread, as reported by procfs.
+///
+/// This differs from `CapState`, as procfs does not report the "keep
+/// capabilities" flag, while it does report the seccomp mode, and fields
+/// missing on older kernels are optional.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, Default)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct ProcCaps {
+    /// Effective capabilities set (`CapEff`).
+    pub effective: CapsBitSet,
+    /// Permitted capabilities set (`CapPrm`).
+    pub permitted: CapsBitSet,
+    /// Inheritable capabilities set (`CapInh`).
+    pub inheritable: CapsBitSet,
+    /// Ambient capabilities set (`CapAmb`), empty on kernels without ambient support.
+    pub ambient: CapsBitSet,
+    /// Bounding capabilities set (`CapBnd`).
+    pub bounding: CapsBitSet,
+    /// "No new privileges" flag (`NoNewPrivs`), from Linux 4.10.
+    pub no_new_privs: Option<bool>,
+    /// Seccomp mode (`Seccomp`), if the kernel supports seccomp.
+    pub seccomp: Option<SeccompMode>,
+}
+
+impl ProcCaps {
+    /// Return the Effective, Permitted and Inheritable sets of this state.
+    pub fn base(&self) -> BaseState {
+        self.sets().base()
+    }
+
+    /// Return the content of set `cset`.
+    pub fn get(&self, cset: CapSet) -> CapsBitSet {
+        self.sets().get(cset)
+    }
+
+    /// Return the five capabilities sets of this state, without flags.
+    fn sets(&self) -> CapState {
+        CapState {
+            effective: self.effective,
+            permitted: self.permitted,
+            inheritable: self.inheritable,
+            ambient: self.ambient,
+            bounding: self.bounding,
+            ..CapState::default()
+        }
+    }
+
+    /// Parse capabilities fields from the content of a procfs `status` file.
+    pub fn from_status(content: &str) -> Result<Self, CapsError> {
+        let mut res = Self::default();
+        let (mut inh, mut prm, mut eff, mut bnd) = (false, false, false, false);
+        for line in content.lines() {
+            let (key, value) = match line.split_once(':') {
+                Some((k, v)) => (k, v.trim()),
+                None => continue,
+            };
+            match key {
+                "CapInh" => {
+                    res.inheritable = parse_mask(key, value)?;
+                    inh = true;
+                }
+                "CapPrm" => {
+                    res.permitted = parse_mask(key, value)?;
+                    prm = true;
+                }
+                "CapEff" => {
+                    res.effective = parse_mask(key, value)?;
+                    eff = true;
+                }
+                "CapBnd" => {
+                    res.bounding = parse_mask(key, value)?;
+                    bnd = true;
+                }
+                "CapAmb" => res.ambient = parse_mask(key, value)?,
+                "NoNewPrivs" => {
+                    res.no_new_privs = match value {
+                        "0" => Some(false),
+                        "1" => Some(true),
+                        _ => return Err(invalid(format!("invalid NoNewPrivs value '{}'", value))),
+                    }
+                }
+                "Seccomp" => {
+                    res.seccomp = match value {
+                        "0" => Some(SeccompMode::Disabled),
+                        "1" => Some(SeccompMode::Strict),
+                        "2" => Some(SeccompMode::Filter),
+                        _ => return Err(invalid(format!("invalid Seccomp value '{}'", value))),
+                    }
+                }
+                _ => {}
+            }
+        }
+        for (found, key) in &[
+            (inh, "CapInh"),
+            (prm, "CapPrm"),
+            (eff, "CapEff"),
+            (bnd, "CapBnd"),
+        ] {
+            if !found {
+                return Err(invalid(format!("missing {} field", key)));
+            }
+        }
+        Ok(res)
+    }
+}
+
+/// Read capabilities state of process `pid`.
+///
+/// This reads `/proc/<pid>/status`, which reports the state of the
+/// process main thread. By default, it uses `/proc/` as the procfs
+/// mountpoint.
+pub fn read_pid(pid: i32, proc_mountpoint: Option<PathBuf>) -> Result<ProcCaps, CapsError> {
+    let path = proc_mountpoint
+        .unwrap_or_else(|| PathBuf::from("/proc/"))
+        .join(pid.to_string())
+        .join("status");
+    read_status(&path)
+}
+
+/// Read capabilities state of thread `tid` in process `pid`.
+///
+/// This reads `/proc/<pid>/task/<tid>/status`. By default, it uses
+/// `/proc/` as the procfs mountpoint.
+pub fn read_tid(
+    pid: i32,
+    tid: i32,
+    proc_mountpoint: Option<PathBuf>,
+) -> Result<ProcCaps, CapsError> {
+    let path = proc_mountpoint
+        .unwrap_or_else(|| PathBuf::from("/proc/"))
+        .join(pid.to_string())
+        .join("task")
+        .join(tid.to_string())
+        .join("status");
+    read_status(&path)
+}
+
+/// Read capabilities state of thread `tid`, from the default procfs mountpoint.
+///
+/// Thread directories are not listed under `/proc/`, but they can be
+/// accessed directly as `/proc/<tid>/`.
+pub(crate) fn read_thread(tid: i32) -> Result<ProcCaps, CapsError> {
+    let procfs = Path::new("/proc/self/status");
+    read_pid(tid, None).map_err(|e| {
+        let e = e.with_tid(tid);
+        if procfs.exists() {
+            e
+        } else {
+            e.wrap("procfs unavailable")
+                .with_kind(ErrorKind::ProcfsUnavailable)
+        }
+    })
+}
+
+fn read_status(path: &Path) -> Result<ProcCaps, CapsError> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| CapsError::io(format_args!("failed to read '{}'", path.display()), e))?;
+    ProcCaps::from_status(&content)
+        .map_err(|e| e.wrap(format_args!("failed to parse '{}'", path.display())))
+}
+
+fn parse_mask(key: &str, value: &str) -> Result<CapsBitSet, CapsError> {
+    CapsBitSet::from_hex(value, UnknownBits::Keep)
+        .map_err(|e| e.wrap(format_args!("invalid {} mask", key)))
+}
+
+fn invalid(msg: String) -> CapsError {
+    CapsError::new(ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Capability;
+
+    #[test]
+    fn test_from_status() {
+        let content = "Name:\tcat\nUmask:\t0022\nState:\tR (running)\n\
+            CapInh:\t0000000000000000\nCapPrm:\t000001ffffffffff\n\
+            CapEff:\t0000000000002000\nCapBnd:\t000001ffffffffff\n\
+            CapAmb:\t0000000000000000\nNoNewPrivs:\t1\nSeccomp:\t2\n\
+            Seccomp_filters:\t1\n";
+        let st = ProcCaps::from_status(content).unwrap();
+        assert!(st.inheritable.is_empty());
+        assert_eq!(st.permitted, CapsBitSet::all());
+        assert_eq!(st.bounding, CapsBitSet::all());
+        assert_eq!(st.effective, CapsBitSet::from(Capability::CAP_NET_RAW));
+        assert!(st.ambient.is_empty());
+        assert_eq!(st.no_new_privs, Some(true));
+        assert_eq!(st.seccomp, Some(SeccompMode::Filter));
+    }
+
+    #[test]
+    fn test_from_status_legacy() {
+        let content = "CapInh:\t0000000000000000\nCapPrm:\t0000000000000001\n\
+            CapEff:\t0000000000000001\nCapBnd:\tffffffffffffffff\n";
+        let st = ProcCaps::from_status(content).unwrap();
+        assert!(st.ambient.is_empty());
+        assert_eq!(st.no_new_privs, None);
+        assert_eq!(st.seccomp, None);
+    }
+
+    #[test]
+    fn test_from_status_invalid() {
+        assert!(ProcCaps::from_status("").is_err());
+        let content = "CapInh:\tzz\nCapPrm:\t0\nCapEff:\t0\nCapBnd:\t0\n";
+        let err = ProcCaps::from_status(content).unwrap_err();
+        assert!(err.to_string().contains("invalid CapInh mask"));
+        let content = "CapInh:\t+0\nCapPrm:\t0\nCapEff:\t0\nCapBnd:\t0\n";
+        let err = ProcCaps::from_status(content).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(err.to_string().contains("invalid CapInh mask"));
+        let content = "CapPrm:\t0\nCapEff:\t0\nCapBnd:\t0\n";
+        let err = ProcCaps::from_status(content).unwrap_err();
+        assert!(err.to_string().contains("missing CapInh"));
+    }
+}
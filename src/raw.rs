@@ -0,0 +1,264 @@
+/*!
+Allocation-free capability primitives.
+
+This module exposes thin wrappers around the `capget(2)`, `capset(2)`
+and `prctl(2)` operations on capabilities. They work on raw `u64` masks
+(bit `n` standing for the capability with index `n`) and report failures
+as plain `errno` values. They never allocate nor take locks, so they
+can be used between `fork(2)` and `execve(2)` or in signal handlers,
+where the rest of this library is not safe to call.
+
+All of them act on the calling thread, unless stated otherwise.
+
+## Example
+
+```rust
+let version = caps::raw::capget_version();
+let masks = caps::raw::capget(version, 0).unwrap();
+let net_raw = 1 << caps::Capability::CAP_NET_RAW.index();
+println!("CAP_NET_RAW effective: {}", masks.effective & net_raw != 0);
+```
+*/
+
+use crate::nr;
+use std::os::raw::c_ulong;
+
+/// Effective, Permitted and Inheritable sets of a thread, as raw masks.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, Default)]
+pub struct BaseMasks {
+    /// Effective capabilities mask.
+    pub effective: u64,
+    /// Permitted capabilities mask.
+    pub permitted: u64,
+    /// Inheritable capabilities mask.
+    pub inheritable: u64,
+}
+
+#[derive(Debug)]
+#[repr(C)]
+struct CapUserHeader {
+    // Linux capabilities version (runtime kernel support)
+    version: u32,
+    // Process ID (thread)
+    pid: i32,
+}
+
+#[derive(Debug, Default, Clone)]
+#[repr(C)]
+struct CapUserData {
+    effective_s0: u32,
+    permitted_s0: u32,
+    inheritable_s0: u32,
+    effective_s1: u32,
+    permitted_s1: u32,
+    inheritable_s1: u32,
+}
+
+/// Return the current `errno` value.
+fn errno() -> i32 {
+    std::io::Error::last_os_error()
+        .raw_os_error()
+        .unwrap_or(libc::EIO)
+}
+
+/// Return the `capget(2)` ABI version preferred by the kernel.
+///
+/// Given an unknown version, the kernel fails with `EINVAL` after writing
+/// back its preferred one.
+pub fn capget_version() -> u32 {
+    let mut hdr = CapUserHeader { version: 0, pid: 0 };
+    let data = std::ptr::null_mut::<CapUserData>();
+    unsafe { libc::syscall(nr::CAPGET, &mut hdr, data) };
+    hdr.version
+}
+
+/// Read the Effective, Permitted and Inheritable sets of thread `tid`.
+///
+/// `version` is the `capget(2)` ABI version to use, see [`capget_version`].
+/// A `tid` of 0 stands for the calling thread.
+///
+/// [`capget_version`]: fn.capget_version.html
+pub fn capget(version: u32, tid: i32) -> Result<BaseMasks, i32> {
+    let mut hdr = CapUserHeader { version, pid: tid };
+    let mut data = CapUserData::default();
+    let r = unsafe { libc::syscall(nr::CAPGET, &mut hdr, &mut data) };
+    if r != 0 {
+        return Err(errno());
+    }
+    let mask = |lo: u32, hi: u32| (u64::from(hi) << 32) | u64::from(lo);
+    Ok(BaseMasks {
+        effective: mask(data.effective_s0, data.effective_s1),
+        permitted: mask(data.permitted_s0, data.permitted_s1),
+        inheritable: mask(data.inheritable_s0, data.inheritable_s1),
+    })
+}
+
+/// Set the Effective, Permitted and Inheritable sets of thread `tid`.
+///
+/// `version` is the `capget(2)` ABI version to use, see [`capget_version`].
+/// A `tid` of 0 stands for the calling thread, which is the only one
+/// modern kernels accept.
+///
+/// [`capget_version`]: fn.capget_version.html
+pub fn capset(version: u32, tid: i32, masks: &BaseMasks) -> Result<(), i32> {
+    let mut hdr = CapUserHeader { version, pid: tid };
+    let data = CapUserData {
+        effective_s0: masks.effective as u32,
+        permitted_s0: masks.permitted as u32,
+        inheritable_s0: masks.inheritable as u32,
+        effective_s1: (masks.effective >> 32) as u32,
+        permitted_s1: (masks.permitted >> 32) as u32,
+        inheritable_s1: (masks.inheritable >> 32) as u32,
+    };
+    let r = unsafe { libc::syscall(nr::CAPSET, &mut hdr, &data) };
+    match r {
+        0 => Ok(()),
+        _ => Err(errno()),
+    }
+}
+
+fn prctl(option: i32, arg2: c_ulong, arg3: c_ulong) -> Result<i32, i32> {
+    let ret = unsafe { libc::prctl(option, arg2, arg3, 0 as c_ulong, 0 as c_ulong) };
+    match ret {
+        r if r < 0 => Err(errno()),
+        r => Ok(r),
+    }
+}
+
+fn prctl_bool(option: i32, arg2: c_ulong, arg3: c_ulong) -> Result<bool, i32> {
+    match prctl(option, arg2, arg3)? {
+        0 => Ok(false),
+        _ => Ok(true),
+    }
+}
+
+/// Return whether capability `index` is in the Bounding set.
+///
+/// This fails with `EINVAL` past the last capability supported by the kernel.
+pub fn bounding_has(index: u8) -> Result<bool, i32> {
+    prctl_bool(nr::PR_CAPBSET_READ, index.into(), 0)
+}
+
+/// Drop capability `index` from the Bounding set.
+///
+/// This requires `CAP_SETPCAP` in the Effective set.
+pub fn bounding_drop(index: u8) -> Result<(), i32> {
+    prctl(nr::PR_CAPBSET_DROP, index.into(), 0).map(|_| ())
+}
+
+/// Read the Bounding set, checking indices up to `last_cap`.
+pub fn bounding_read(last_cap: u8) -> Result<u64, i32> {
+    let mut mask = 0;
+    for index in 0..=last_cap.min(63) {
+        if bounding_has(index)? {
+            mask |= 1 << index;
+        }
+    }
+    Ok(mask)
+}
+
+/// Drop all capabilities up to `last_cap` which are not in `keep` from the Bounding set.
+pub fn bounding_restrict(keep: u64, last_cap: u8) -> Result<(), i32> {
+    for index in 0..=last_cap.min(63) {
+        if keep & (1 << index) == 0 {
+            bounding_drop(index)?;
+        }
+    }
+    Ok(())
+}
+
+/// Return whether capability `index` is in the Ambient set.
+pub fn ambient_has(index: u8) -> Result<bool, i32> {
+    prctl_bool(
+        nr::PR_CAP_AMBIENT,
+        nr::PR_CAP_AMBIENT_IS_SET as c_ulong,
+        index.into(),
+    )
+}
+
+/// Raise capability `index` in the Ambient set.
+///
+/// The capability must be in both the Permitted and Inheritable sets.
+pub fn ambient_raise(index: u8) -> Result<(), i32> {
+    prctl(
+        nr::PR_CAP_AMBIENT,
+        nr::PR_CAP_AMBIENT_RAISE as c_ulong,
+        index.into(),
+    )
+    .map(|_| ())
+}
+
+/// Lower capability `index` in the Ambient set.
+pub fn ambient_lower(index: u8) -> Result<(), i32> {
+    prctl(
+        nr::PR_CAP_AMBIENT,
+        nr::PR_CAP_AMBIENT_LOWER as c_ulong,
+        index.into(),
+    )
+    .map(|_| ())
+}
+
+/// Clear the Ambient set.
+pub fn ambient_clear() -> Result<(), i32> {
+    prctl(
+        nr::PR_CAP_AMBIENT,
+        nr::PR_CAP_AMBIENT_CLEAR_ALL as c_ulong,
+        0,
+    )
+    .map(|_| ())
+}
+
+/// Read the Ambient set, checking indices up to `last_cap`.
+pub fn ambient_read(last_cap: u8) -> Result<u64, i32> {
+    let mut mask = 0;
+    for index in 0..=last_cap.min(63) {
+        if ambient_has(index)? {
+            mask |= 1 << index;
+        }
+    }
+    Ok(mask)
+}
+
+/// Set the Ambient set to `mask`, for indices up to `last_cap`.
+pub fn ambient_write(mask: u64, last_cap: u8) -> Result<(), i32> {
+    for index in 0..=last_cap.min(63) {
+        if mask & (1 << index) != 0 {
+            ambient_raise(index)?;
+        } else {
+            ambient_lower(index)?;
+        }
+    }
+    Ok(())
+}
+
+/// Return the securebits flags.
+pub fn get_securebits() -> Result<u32, i32> {
+    prctl(nr::PR_GET_SECUREBITS, 0, 0).map(|bits| bits as u32)
+}
+
+/// Set the securebits flags to `bits`.
+///
+/// This requires `CAP_SETPCAP` in the Effective set.
+pub fn set_securebits(bits: u32) -> Result<(), i32> {
+    prctl(nr::PR_SET_SECUREBITS, bits.into(), 0).map(|_| ())
+}
+
+/// Return whether the "keep capabilities" flag is set.
+pub fn get_keepcaps() -> Result<bool, i32> {
+    prctl_bool(nr::PR_GET_KEEPCAPS, 0, 0)
+}
+
+/// Set the value of the "keep capabilities" flag.
+pub fn set_keepcaps(keep_caps: bool) -> Result<(), i32> {
+    prctl(nr::PR_SET_KEEPCAPS, keep_caps as c_ulong, 0).map(|_| ())
+}
+
+/// Return whether the "no new privileges" flag is set.
+pub fn get_no_new_privs() -> Result<bool, i32> {
+    prctl_bool(nr::PR_GET_NO_NEW_PRIVS, 0, 0)
+}
+
+/// Set the "no new privileges" flag, which cannot be cleared afterwards.
+pub fn set_no_new_privs() -> Result<(), i32> {
+    prctl(nr::PR_SET_NO_NEW_PRIVS, 1, 0).map(|_| ())
+}
@@ -18,10 +18,11 @@ println!("Supported capabilities: {}", all.len());
 ```
 */
 
-use super::{ambient, CapSet, Capability, CapsHashSet};
-use crate::errors::CapsError;
+use super::{raw, CapsBitSet, CapsHashSet};
+use crate::errors::{CapsError, ErrorKind};
 use std::io::Read;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
 
 /// Check whether the running kernel supports the ambient set.
 ///
@@ -29,52 +30,218 @@ use std::path::{Path, PathBuf};
 /// where the ambient set is supported, this will return `Ok`.
 /// On a legacy kernel, an `Err` is returned instead.
 pub fn ambient_set_supported() -> Result<(), CapsError> {
-    ambient::has_cap(Capability::CAP_CHOWN)?;
-    Ok(())
+    if has_ambient() {
+        Ok(())
+    } else {
+        Err(CapsError::new(
+            ErrorKind::KernelTooOld,
+            "ambient set is not supported",
+        ))
+    }
 }
 
 /// Return the set of all capabilities supported by the running kernel.
 ///
 /// This requires a mounted `procfs` and a kernel version >= 3.2. By default,
 /// it uses `/proc/` as the procfs mountpoint.
+/// Capabilities unknown to this library are not part of the result, use
+/// [`procfs_last_cap`] to detect them.
+///
+/// [`procfs_last_cap`]: fn.procfs_last_cap.html
 pub fn procfs_all_supported(proc_mountpoint: Option<PathBuf>) -> Result<CapsHashSet, CapsError> {
+    let max_cap = procfs_last_cap(proc_mountpoint)?;
+    let mut supported = super::all();
+    for c in super::all() {
+        if c.index() > max_cap {
+            supported.remove(&c);
+        }
+    }
+    Ok(supported)
+}
+
+/// Return the index of the last capability supported by the running kernel.
+///
+/// This can be higher than the index of any capability known to this
+/// library, on kernels newer than it; `CapsBitSet::up_to` turns it into
+/// the full set of supported capabilities.
+/// This requires a mounted `procfs` and a kernel version >= 3.2. By default,
+/// it uses `/proc/` as the procfs mountpoint.
+pub fn procfs_last_cap(proc_mountpoint: Option<PathBuf>) -> Result<u8, CapsError> {
     /// See `man 2 capabilities`.
     const LAST_CAP_FILEPATH: &str = "./sys/kernel/cap_last_cap";
     let last_cap_path = proc_mountpoint
         .unwrap_or_else(|| PathBuf::from("/proc/"))
         .join(Path::new(LAST_CAP_FILEPATH));
 
-    let max_cap: u8 = {
-        let mut buf = String::with_capacity(4);
-        std::fs::File::open(last_cap_path.clone())
-            .and_then(|mut file| file.read_to_string(&mut buf))
-            .map_err(|e| format!("failed to read '{}': {}", last_cap_path.display(), e))?;
-        buf.trim_end()
-            .parse()
-            .map_err(|e| format!("failed to parse '{}': {}", last_cap_path.display(), e))?
-    };
+    let mut buf = String::with_capacity(4);
+    std::fs::File::open(last_cap_path.clone())
+        .and_then(|mut file| file.read_to_string(&mut buf))
+        .map_err(|e| {
+            CapsError::io(
+                format_args!("failed to read '{}'", last_cap_path.display()),
+                e,
+            )
+            .with_kind(ErrorKind::ProcfsUnavailable)
+        })?;
+    buf.trim_end().parse().map_err(|e| {
+        CapsError::new(
+            ErrorKind::InvalidData,
+            format!("failed to parse '{}': {}", last_cap_path.display(), e),
+        )
+    })
+}
 
-    let mut supported = super::all();
-    for c in super::all() {
-        if c.index() > max_cap {
-            supported.remove(&c);
+/// Kernel features related to capabilities, detected at runtime.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub struct Features {
+    /// Index of the last capability supported by the kernel.
+    pub last_cap: u8,
+    /// Whether the Ambient set is supported (Linux >= 4.3).
+    pub ambient: bool,
+    /// Whether securebits can be read and set (Linux >= 2.6.26).
+    pub securebits: bool,
+    /// `capget(2)` ABI version preferred by the kernel.
+    pub capget_version: u32,
+}
+
+impl Features {
+    /// Return the set of all capabilities supported by the kernel.
+    ///
+    /// This includes capabilities unknown to this library.
+    pub fn supported(&self) -> CapsBitSet {
+        CapsBitSet::up_to(self.last_cap)
+    }
+}
+
+// Cached detection results. Each one is 0 until detected, then written
+// once with `DETECTED` set. Concurrent first uses may both run detection,
+// which is harmless as they get the same result.
+static LAST_CAP: AtomicU32 = AtomicU32::new(0);
+static AMBIENT: AtomicU32 = AtomicU32::new(0);
+static SECUREBITS: AtomicU32 = AtomicU32::new(0);
+static CAPGET_VERSION: AtomicU32 = AtomicU32::new(0);
+
+/// Marks a cached value as detected.
+const DETECTED: u32 = 1 << 31;
+/// Marks a cached value as a failed detection, holding the `errno` value.
+const FAILED: u32 = 1 << 30;
+
+/// Return the value cached in `cell`, running `detect` on first use.
+fn cached(cell: &AtomicU32, detect: fn() -> u32) -> u32 {
+    match cell.load(Ordering::Acquire) {
+        0 => {
+            let value = detect() | DETECTED;
+            cell.store(value, Ordering::Release);
+            value & !DETECTED
         }
+        value => value & !DETECTED,
     }
-    Ok(supported)
+}
+
+/// Return the kernel features related to capabilities.
+///
+/// Features are detected on first use, without requiring a mounted
+/// `procfs`, and cached for the lifetime of the process. All functions
+/// in this library consult this cache instead of probing the kernel again.
+/// The cache is lock-free, and failed detections are cached as well.
+/// It requires a kernel version >= 2.6.25.
+pub fn features() -> Result<Features, CapsError> {
+    Ok(Features {
+        last_cap: last_cap()?,
+        ambient: has_ambient(),
+        securebits: has_securebits(),
+        capget_version: capget_version(),
+    })
+}
+
+/// Forget cached kernel features, so that they are detected again on next use.
+///
+/// This is mostly useful for tests.
+pub fn reset_features() {
+    for cell in &[&LAST_CAP, &AMBIENT, &SECUREBITS, &CAPGET_VERSION] {
+        cell.store(0, Ordering::Release);
+    }
+}
+
+/// Return the index of the last capability supported by the running kernel.
+///
+/// This does not require a mounted `procfs`, and it works with any
+/// kernel version >= 2.6.25. It binary-searches capability indices with
+/// `prctl(2)` and `PR_CAPBSET_READ`, which fails with `EINVAL` past the
+/// last supported one, so it also detects capabilities unknown to this library.
+/// The result is cached for the lifetime of the process, see [`features`].
+///
+/// [`features`]: fn.features.html
+pub fn last_cap() -> Result<u8, CapsError> {
+    let value = cached(&LAST_CAP, || match probe_last_cap() {
+        Ok(last_cap) => u32::from(last_cap),
+        Err(errno) => FAILED | errno as u32,
+    });
+    if value & FAILED == 0 {
+        return Ok(value as u8);
+    }
+    let errno = (value & !FAILED) as i32;
+    Err(CapsError::from_errno("PR_CAPBSET_READ", errno)
+        .wrap("failed to detect last capability")
+        .with_kind(ErrorKind::KernelTooOld))
+}
+
+/// Return whether the Ambient set is supported, see [`features`].
+///
+/// [`features`]: fn.features.html
+pub(crate) fn has_ambient() -> bool {
+    cached(&AMBIENT, || u32::from(raw::ambient_has(0).is_ok())) != 0
+}
+
+/// Return whether securebits are supported, see [`features`].
+///
+/// [`features`]: fn.features.html
+pub(crate) fn has_securebits() -> bool {
+    cached(&SECUREBITS, || u32::from(raw::get_securebits().is_ok())) != 0
+}
+
+/// Return the `capget(2)` ABI version preferred by the kernel, see [`features`].
+///
+/// [`features`]: fn.features.html
+pub(crate) fn capget_version() -> u32 {
+    cached(&CAPGET_VERSION, raw::capget_version)
+}
+
+fn probe_last_cap() -> Result<u8, i32> {
+    let supported = |index: u8| -> Result<bool, i32> {
+        match raw::bounding_has(index) {
+            Ok(_) => Ok(true),
+            Err(libc::EINVAL) => Ok(false),
+            Err(e) => Err(e),
+        }
+    };
+    if !supported(0)? {
+        return Err(libc::EINVAL);
+    }
+    // Invariant: `low` is supported, `high` is not.
+    let (mut low, mut high) = (0u8, 64u8);
+    while high - low > 1 {
+        let mid = low + (high - low) / 2;
+        if supported(mid)? {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    Ok(low)
 }
 
 /// Return the set of all capabilities supported on the current thread.
 ///
 /// This does not require a mounted `procfs`, and it works with any
 /// kernel version >= 2.6.25.
-/// It internally uses `prctl(2)` and `PR_CAPBSET_READ`; if those are
+/// It is based on [`last_cap`]; if `PR_CAPBSET_READ` is
 /// unavailable, this will result in an empty set.
+///
+/// [`last_cap`]: fn.last_cap.html
 pub fn thread_all_supported() -> CapsHashSet {
-    let mut supported = super::all();
-    for c in super::all() {
-        if super::has_cap(None, CapSet::Bounding, c).is_err() {
-            supported.remove(&c);
-        }
+    match last_cap() {
+        Ok(last_cap) => CapsHashSet::from(CapsBitSet::up_to(last_cap)),
+        Err(_) => CapsHashSet::new(),
     }
-    supported
 }
@@ -2,34 +2,229 @@
 //!
 //! This module exposes methods to get and set per-thread securebits
 //! flags, which can be used to disable special handling of capabilities
-//! for UID 0 (root).
+//! for UID 0 (root), as well as the related "no new privileges" flag.
 
-use crate::errors::CapsError;
+use crate::errors::{CapsError, ErrorKind};
 use crate::nr;
-use std::io::Error;
+use crate::raw;
+use crate::runtime;
 
 /// Return whether the current thread's "keep capabilities" flag is set.
 pub fn has_keepcaps() -> Result<bool, CapsError> {
-    let ret = unsafe { libc::prctl(nr::PR_GET_KEEPCAPS, 0, 0, 0) };
-    match ret {
-        0 => Ok(false),
-        1 => Ok(true),
-        _ => Err(CapsError::from(format!(
-            "PR_GET_KEEPCAPS failure: {}",
-            Error::last_os_error()
-        ))),
-    }
+    raw::get_keepcaps().map_err(|e| CapsError::from_errno("PR_GET_KEEPCAPS", e))
 }
 
 /// Set the value of the current thread's "keep capabilities" flag.
 pub fn set_keepcaps(keep_caps: bool) -> Result<(), CapsError> {
-    let flag = if keep_caps { 1 } else { 0 };
-    let ret = unsafe { libc::prctl(nr::PR_SET_KEEPCAPS, flag, 0, 0) };
-    match ret {
-        0 => Ok(()),
-        _ => Err(CapsError::from(format!(
-            "PR_SET_KEEPCAPS failure: {}",
-            Error::last_os_error()
-        ))),
+    raw::set_keepcaps(keep_caps).map_err(|e| CapsError::from_errno("PR_SET_KEEPCAPS", e))
+}
+
+/// Set the current thread's "no new privileges" flag.
+///
+/// Once set, this flag cannot be cleared, and it is inherited by children.
+/// It makes `execve(2)` ignore setuid/setgid bits and file capabilities.
+pub fn set_no_new_privs() -> Result<(), CapsError> {
+    raw::set_no_new_privs().map_err(|e| {
+        CapsError::from_errno("PR_SET_NO_NEW_PRIVS", e).on_einval(ErrorKind::KernelTooOld)
+    })
+}
+
+/// Return whether the current thread's "no new privileges" flag is set.
+pub fn get_no_new_privs() -> Result<bool, CapsError> {
+    raw::get_no_new_privs().map_err(|e| {
+        CapsError::from_errno("PR_GET_NO_NEW_PRIVS", e).on_einval(ErrorKind::KernelTooOld)
+    })
+}
+
+/// Per-thread securebits flags.
+///
+/// This is a set of the securebits flags defined by the kernel,
+/// see `capabilities(7)`. Each base flag has a `_LOCKED` companion which,
+/// once set, prevents any further change to it (and cannot be cleared).
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, Default)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct SecureBits(u32);
+
+impl SecureBits {
+    /// `SECURE_NOROOT`: UID 0 does not gain capabilities on `execve(2)`.
+    pub const NOROOT: Self = Self(1 << nr::SECURE_NOROOT);
+    /// `SECURE_NOROOT_LOCKED`: lock `SECURE_NOROOT`.
+    pub const NOROOT_LOCKED: Self = Self(1 << nr::SECURE_NOROOT_LOCKED);
+    /// `SECURE_NO_SETUID_FIXUP`: UID transitions do not adjust capabilities.
+    pub const NO_SETUID_FIXUP: Self = Self(1 << nr::SECURE_NO_SETUID_FIXUP);
+    /// `SECURE_NO_SETUID_FIXUP_LOCKED`: lock `SECURE_NO_SETUID_FIXUP`.
+    pub const NO_SETUID_FIXUP_LOCKED: Self = Self(1 << nr::SECURE_NO_SETUID_FIXUP_LOCKED);
+    /// `SECURE_KEEP_CAPS`: keep permitted capabilities when dropping UID 0.
+    pub const KEEP_CAPS: Self = Self(1 << nr::SECURE_KEEP_CAPS);
+    /// `SECURE_KEEP_CAPS_LOCKED`: lock `SECURE_KEEP_CAPS`.
+    pub const KEEP_CAPS_LOCKED: Self = Self(1 << nr::SECURE_KEEP_CAPS_LOCKED);
+    /// `SECURE_NO_CAP_AMBIENT_RAISE`: forbid raising ambient capabilities.
+    pub const NO_CAP_AMBIENT_RAISE: Self = Self(1 << nr::SECURE_NO_CAP_AMBIENT_RAISE);
+    /// `SECURE_NO_CAP_AMBIENT_RAISE_LOCKED`: lock `SECURE_NO_CAP_AMBIENT_RAISE`.
+    pub const NO_CAP_AMBIENT_RAISE_LOCKED: Self = Self(1 << nr::SECURE_NO_CAP_AMBIENT_RAISE_LOCKED);
+
+    /// All known flags, paired with their names and lock flags.
+    const FLAGS: [(Self, &'static str, Self); 4] = [
+        (Self::NOROOT, "SECURE_NOROOT", Self::NOROOT_LOCKED),
+        (
+            Self::NO_SETUID_FIXUP,
+            "SECURE_NO_SETUID_FIXUP",
+            Self::NO_SETUID_FIXUP_LOCKED,
+        ),
+        (Self::KEEP_CAPS, "SECURE_KEEP_CAPS", Self::KEEP_CAPS_LOCKED),
+        (
+            Self::NO_CAP_AMBIENT_RAISE,
+            "SECURE_NO_CAP_AMBIENT_RAISE",
+            Self::NO_CAP_AMBIENT_RAISE_LOCKED,
+        ),
+    ];
+
+    /// Return an empty set of flags.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Return the set of all known flags.
+    pub const fn all() -> Self {
+        Self(0xff)
+    }
+
+    /// Return the raw value of these flags.
+    pub const fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// Build a set of flags from a raw value, rejecting unknown bits.
+    pub fn from_bits(bits: u32) -> Option<Self> {
+        match bits & !Self::all().0 {
+            0 => Some(Self(bits)),
+            _ => None,
+        }
+    }
+
+    /// Return whether all flags in `other` are set.
+    pub fn contains(&self, other: Self) -> bool {
+        (self.0 & other.0) == other.0
+    }
+
+    /// Set all flags in `other`.
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+
+    /// Clear all flags in `other`.
+    pub fn remove(&mut self, other: Self) {
+        self.0 &= !other.0;
+    }
+
+    /// Return whether no flag is set.
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Return the lock flags matching all base flags in this set.
+    pub fn locks(&self) -> Self {
+        Self((self.0 & 0x55) << 1)
     }
+
+    /// Check whether the kernel would allow a transition from `current` to this set.
+    ///
+    /// The returned error names the locked flag which blocks the change.
+    pub fn check_transition(&self, current: Self) -> Result<(), CapsError> {
+        for (flag, name, lock) in &Self::FLAGS {
+            if current.contains(*lock) {
+                if self.contains(*flag) != current.contains(*flag) {
+                    return Err(CapsError::new(
+                        ErrorKind::Locked,
+                        format!("{} is locked by {}_LOCKED", name, name),
+                    ));
+                }
+                if !self.contains(*lock) {
+                    return Err(CapsError::new(
+                        ErrorKind::Locked,
+                        format!("{}_LOCKED cannot be cleared", name),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::ops::BitOr for SecureBits {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::BitAnd for SecureBits {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl std::ops::Sub for SecureBits {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self(self.0 & !rhs.0)
+    }
+}
+
+impl std::fmt::Display for SecureBits {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut first = true;
+        for (flag, name, lock) in &Self::FLAGS {
+            for (bit, suffix) in &[(*flag, ""), (*lock, "_LOCKED")] {
+                if self.contains(*bit) {
+                    if !first {
+                        write!(f, "|")?;
+                    }
+                    write!(f, "{}{}", name, suffix)?;
+                    first = false;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Return the current thread's securebits flags.
+pub fn read() -> Result<SecureBits, CapsError> {
+    if !runtime::has_securebits() {
+        return Err(CapsError::new(
+            ErrorKind::KernelTooOld,
+            "securebits are not supported",
+        ));
+    }
+    raw::get_securebits().map(SecureBits).map_err(|e| {
+        CapsError::from_errno("PR_GET_SECUREBITS", e).on_einval(ErrorKind::KernelTooOld)
+    })
+}
+
+/// Set the current thread's securebits flags to `value`.
+///
+/// This requires `CAP_SETPCAP` in the effective set. Changes to locked
+/// flags are rejected, with an error naming the blocking lock.
+pub fn set(value: SecureBits) -> Result<(), CapsError> {
+    let current = read()?;
+    value.check_transition(current)?;
+    raw::set_securebits(value.bits()).map_err(|e| CapsError::from_errno("PR_SET_SECUREBITS", e))
+}
+
+/// Set the given flags, in addition to the current thread's securebits.
+pub fn add(flags: SecureBits) -> Result<(), CapsError> {
+    set(read()? | flags)
+}
+
+/// Clear the given flags from the current thread's securebits.
+pub fn remove(flags: SecureBits) -> Result<(), CapsError> {
+    set(read()? - flags)
 }
@@ -0,0 +1,362 @@
+//! Implementation of thread capabilities state.
+
+use crate::errors::{CapsError, ErrorKind};
+use crate::{ambient, base, bounding, runtime, securebits, text};
+use crate::{CapSet, Capability, CapsBitSet};
+
+/// A snapshot of all capabilities sets of a thread.
+///
+/// This holds all five capabilities sets (Effective, Permitted,
+/// Inheritable, Ambient, Bounding) together with the "keep capabilities"
+/// and "no new privileges" flags, so that a thread state can be logged,
+/// compared and restored as a single unit.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, Default)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct CapState {
+    /// Effective capabilities set.
+    pub effective: CapsBitSet,
+    /// Permitted capabilities set.
+    pub permitted: CapsBitSet,
+    /// Inheritable capabilities set.
+    pub inheritable: CapsBitSet,
+    /// Ambient capabilities set.
+    pub ambient: CapsBitSet,
+    /// Bounding capabilities set.
+    pub bounding: CapsBitSet,
+    /// "Keep capabilities" securebits flag.
+    pub keepcaps: bool,
+    /// "No new privileges" flag.
+    pub no_new_privs: bool,
+}
+
+impl CapState {
+    /// Read the capabilities state of the current thread.
+    ///
+    /// Effective, Permitted and Inheritable sets are read with a single
+    /// `capget(2)` call. On kernels without the Ambient set, it is empty.
+    pub fn current() -> Result<Self, CapsError> {
+        let base = base::read_all(0)?;
+        let ambient = if runtime::has_ambient() {
+            ambient::read_bits()?
+        } else {
+            CapsBitSet::new()
+        };
+        Ok(Self {
+            effective: base.effective,
+            permitted: base.permitted,
+            inheritable: base.inheritable,
+            ambient,
+            bounding: bounding::read_bits()?,
+            keepcaps: securebits::has_keepcaps()?,
+            no_new_privs: securebits::get_no_new_privs()?,
+        })
+    }
+
+    /// Return the Effective, Permitted and Inheritable sets of this state.
+    pub fn base(&self) -> BaseState {
+        BaseState {
+            effective: self.effective,
+            permitted: self.permitted,
+            inheritable: self.inheritable,
+        }
+    }
+
+    /// Return the content of set `cset`.
+    pub fn get(&self, cset: CapSet) -> CapsBitSet {
+        match cset {
+            CapSet::Ambient => self.ambient,
+            CapSet::Bounding => self.bounding,
+            CapSet::Effective => self.effective,
+            CapSet::Inheritable => self.inheritable,
+            CapSet::Permitted => self.permitted,
+        }
+    }
+
+    /// Check that this state respects the invariants enforced by the kernel.
+    ///
+    /// Effective set must be a subset of Permitted set, and Ambient set must
+    /// be a subset of both Permitted and Inheritable sets.
+    /// See `capabilities(7)`.
+    pub fn validate(&self) -> Result<(), CapsError> {
+        check_subset("effective", self.effective, "permitted", self.permitted)?;
+        check_subset("ambient", self.ambient, "permitted", self.permitted)?;
+        check_subset("ambient", self.ambient, "inheritable", self.inheritable)?;
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for CapState {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (name, set) in &[
+            ("effective", self.effective),
+            ("permitted", self.permitted),
+            ("inheritable", self.inheritable),
+            ("ambient", self.ambient),
+            ("bounding", self.bounding),
+        ] {
+            write!(f, "{}={{", name)?;
+            for (i, index) in set.indices().enumerate() {
+                if i > 0 {
+                    write!(f, ",")?;
+                }
+                write!(f, "{}", text::cap_name(index))?;
+            }
+            write!(f, "}} ")?;
+        }
+        write!(
+            f,
+            "keepcaps={} no_new_privs={}",
+            self.keepcaps, self.no_new_privs
+        )
+    }
+}
+
+/// POSIX capabilities sets of a thread.
+///
+/// This holds the Effective, Permitted and Inheritable sets, which the
+/// kernel reads and writes together through `capget(2)` and `capset(2)`.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, Default)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct BaseState {
+    /// Effective capabilities set.
+    pub effective: CapsBitSet,
+    /// Permitted capabilities set.
+    pub permitted: CapsBitSet,
+    /// Inheritable capabilities set.
+    pub inheritable: CapsBitSet,
+}
+
+impl BaseState {
+    /// Return the content of set `cset`.
+    ///
+    /// Only Effective, Permitted and Inheritable sets are available.
+    pub fn get(&self, cset: CapSet) -> Result<CapsBitSet, CapsError> {
+        match cset {
+            CapSet::Effective => Ok(self.effective),
+            CapSet::Inheritable => Ok(self.inheritable),
+            CapSet::Permitted => Ok(self.permitted),
+            CapSet::Bounding | CapSet::Ambient => {
+                Err(CapsError::new(ErrorKind::InvalidInput, "not a base set").with_set(cset))
+            }
+        }
+    }
+
+    /// Replace the content of set `cset` with `value`.
+    ///
+    /// Only Effective, Permitted and Inheritable sets are available.
+    pub fn set(&mut self, cset: CapSet, value: CapsBitSet) -> Result<(), CapsError> {
+        *self.get_mut(cset)? = value;
+        Ok(())
+    }
+
+    /// Raise capability `cap` in set `cset`.
+    ///
+    /// This only updates the snapshot, returning whether it changed.
+    /// Use `caps::set_base` to apply it to a thread.
+    pub fn raise(&mut self, cset: CapSet, cap: Capability) -> Result<bool, CapsError> {
+        Ok(self.get_mut(cset)?.insert(cap))
+    }
+
+    /// Drop capability `cap` from set `cset`.
+    ///
+    /// This only updates the snapshot, returning whether it changed.
+    /// Use `caps::set_base` to apply it to a thread.
+    pub fn drop(&mut self, cset: CapSet, cap: Capability) -> Result<bool, CapsError> {
+        Ok(self.get_mut(cset)?.remove(cap))
+    }
+
+    fn get_mut(&mut self, cset: CapSet) -> Result<&mut CapsBitSet, CapsError> {
+        match cset {
+            CapSet::Effective => Ok(&mut self.effective),
+            CapSet::Inheritable => Ok(&mut self.inheritable),
+            CapSet::Permitted => Ok(&mut self.permitted),
+            CapSet::Bounding | CapSet::Ambient => {
+                Err(CapsError::new(ErrorKind::InvalidInput, "not a base set").with_set(cset))
+            }
+        }
+    }
+
+    /// Check whether the kernel would accept a transition from `current` to this state.
+    ///
+    /// `bounding` is the Bounding set of the thread, and `setpcap` tells
+    /// whether `CAP_SETPCAP` is in its Effective set. This mirrors the
+    /// checks performed by `capset(2)`, and reports the first violated one.
+    pub fn validate_transition(
+        &self,
+        current: &BaseState,
+        bounding: CapsBitSet,
+        setpcap: bool,
+    ) -> Result<(), CapsError> {
+        check_subset("effective", self.effective, "permitted", self.permitted)?;
+        check_subset(
+            "permitted",
+            self.permitted,
+            "current permitted",
+            current.permitted,
+        )?;
+        if !setpcap {
+            check_subset(
+                "inheritable",
+                self.inheritable,
+                "current inheritable and permitted",
+                current.inheritable | current.permitted,
+            )?;
+        }
+        check_subset(
+            "inheritable",
+            self.inheritable,
+            "current inheritable and bounding",
+            current.inheritable | bounding,
+        )?;
+        Ok(())
+    }
+}
+
+/// Check that `subset` is included in `superset`, reporting the offending capabilities.
+fn check_subset(
+    sub_name: &str,
+    subset: CapsBitSet,
+    super_name: &str,
+    superset: CapsBitSet,
+) -> Result<(), CapsError> {
+    let extra = subset - superset;
+    let index = match extra.indices().next() {
+        None => return Ok(()),
+        Some(index) => index,
+    };
+    let mut err = CapsError::new(
+        ErrorKind::InvalidInput,
+        format!(
+            "{} set is not a subset of {} set ({} extra capabilities, including {})",
+            sub_name,
+            super_name,
+            extra.len(),
+            text::cap_name(index)
+        ),
+    );
+    if let Some(c) = Capability::from_index(index) {
+        err = err.with_cap(c);
+    }
+    Err(err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let mut state = CapState::default();
+        state.validate().unwrap();
+
+        state.effective.insert(Capability::CAP_CHOWN);
+        let err = state.validate().unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("effective set is not a subset of permitted"));
+        state.permitted.insert(Capability::CAP_CHOWN);
+        state.validate().unwrap();
+
+        state.ambient.insert(Capability::CAP_CHOWN);
+        let err = state.validate().unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("ambient set is not a subset of inheritable"));
+        state.inheritable.insert(Capability::CAP_CHOWN);
+        state.validate().unwrap();
+
+        // Capabilities unknown to this library are checked too.
+        state.effective.insert_index(41);
+        let err = state.validate().unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("1 extra capabilities, including cap_41"));
+        assert_eq!(err.capability(), None);
+        state.permitted.insert_index(41);
+        state.validate().unwrap();
+    }
+
+    #[test]
+    fn test_validate_transition() {
+        let chown = CapsBitSet::from(Capability::CAP_CHOWN);
+        let kill = CapsBitSet::from(Capability::CAP_KILL);
+        let current = BaseState {
+            effective: chown,
+            permitted: chown,
+            inheritable: CapsBitSet::new(),
+        };
+
+        let mut next = BaseState::default();
+        next.validate_transition(&current, CapsBitSet::all(), false)
+            .unwrap();
+
+        next.effective = chown;
+        let err = next
+            .validate_transition(&current, CapsBitSet::all(), false)
+            .unwrap_err();
+        assert!(err.to_string().contains("effective set is not a subset"));
+
+        next.permitted = chown | kill;
+        next.effective = CapsBitSet::new();
+        let err = next
+            .validate_transition(&current, CapsBitSet::all(), false)
+            .unwrap_err();
+        assert!(err.to_string().contains("permitted set is not a subset"));
+
+        next.permitted = chown;
+        next.inheritable = kill;
+        let err = next
+            .validate_transition(&current, CapsBitSet::all(), false)
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("current inheritable and permitted"));
+        next.validate_transition(&current, CapsBitSet::all(), true)
+            .unwrap();
+        let err = next.validate_transition(&current, chown, true).unwrap_err();
+        assert!(err.to_string().contains("current inheritable and bounding"));
+
+        let mut unknown = BaseState::default();
+        unknown.permitted.insert_index(41);
+        let err = unknown
+            .validate_transition(&current, CapsBitSet::all(), true)
+            .unwrap_err();
+        assert!(err.to_string().contains("permitted set is not a subset"));
+        assert!(err.to_string().contains("cap_41"));
+    }
+
+    #[test]
+    fn test_base_snapshot() {
+        let mut base = BaseState::default();
+        assert!(base.raise(CapSet::Permitted, Capability::CAP_KILL).unwrap());
+        assert!(!base.raise(CapSet::Permitted, Capability::CAP_KILL).unwrap());
+        assert!(base
+            .get(CapSet::Permitted)
+            .unwrap()
+            .contains(Capability::CAP_KILL));
+        assert!(base.drop(CapSet::Permitted, Capability::CAP_KILL).unwrap());
+        base.set(CapSet::Inheritable, CapsBitSet::all()).unwrap();
+        assert_eq!(base.inheritable, CapsBitSet::all());
+        assert!(base.get(CapSet::Ambient).is_err());
+        assert!(base.raise(CapSet::Bounding, Capability::CAP_KILL).is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        let mut state = CapState::default();
+        state.effective.insert(Capability::CAP_KILL);
+        state.effective.insert(Capability::CAP_CHOWN);
+        state.bounding.insert_index(41);
+        state.keepcaps = true;
+        assert_eq!(
+            state.to_string(),
+            "effective={cap_chown,cap_kill} permitted={} inheritable={} ambient={} bounding={cap_41} keepcaps=true no_new_privs=false"
+        );
+    }
+}
@@ -0,0 +1,486 @@
+/*!
+Handle capabilities in libcap textual format.
+
+This module exposes methods to convert between POSIX capabilities
+sets and the textual representation used by libcap tools such as
+`setcap(8)`, `getcap(8)` and `getpcaps(8)`. See `cap_from_text(3)`.
+
+## Example
+
+```rust
+use caps::Capability;
+
+let state = caps::text::from_text("cap_net_raw,cap_net_admin+eip cap_sys_nice=p").unwrap();
+assert!(state.effective.contains(Capability::CAP_NET_ADMIN));
+assert!(state.permitted.contains(Capability::CAP_SYS_NICE));
+assert!(!state.inheritable.contains(Capability::CAP_SYS_NICE));
+
+let text = caps::text::to_text(&state);
+assert_eq!(caps::text::from_text(&text).unwrap(), state);
+```
+*/
+
+use crate::errors::{CapsError, ErrorKind};
+use crate::runtime;
+use crate::{BaseState, Capability, CapsBitSet};
+
+/// Parse a capabilities state from libcap textual format.
+///
+/// The input is a whitespace-separated list of clauses, each made of
+/// a comma-separated list of capability names (or `all`) followed by one
+/// or more actions. An action is an operator (`=`, `+` or `-`) followed by
+/// a list of flags (`e`, `i`, `p`) selecting the affected sets.
+/// Clauses are applied left to right, starting from an empty state.
+///
+/// An empty capability list is only allowed before `=`, and stands for
+/// all capabilities (e.g. `=ep`), as does `all`: those are the ones known
+/// to this library and the ones supported by the running kernel (as
+/// reported by `runtime::last_cap`), like `cap_from_text(3)` does.
+/// Other capabilities unknown to this library can be named by index, as
+/// `cap_41` or `41`.
+/// Errors report the byte offset in `text` where parsing failed.
+pub fn from_text(text: &str) -> Result<BaseState, CapsError> {
+    from_text_with(text, kernel_supported())
+}
+
+/// Parse a capabilities state from libcap textual format, given the set of supported capabilities.
+///
+/// This behaves like [`from_text`], but `all` stands for the capabilities
+/// known to this library and the ones in `supported`, instead of querying
+/// the running kernel.
+///
+/// [`from_text`]: fn.from_text.html
+pub fn from_text_with(text: &str, supported: CapsBitSet) -> Result<BaseState, CapsError> {
+    let mut parser = Parser {
+        text,
+        bytes: text.as_bytes(),
+        pos: 0,
+        all: CapsBitSet::all() | supported,
+    };
+    let mut state = BaseState::default();
+    loop {
+        parser.skip_spaces();
+        if parser.peek().is_none() {
+            return Ok(state);
+        }
+        parser.clause(&mut state)?;
+    }
+}
+
+/// Format a capabilities state in libcap textual format.
+///
+/// This produces the same shortest canonical form as `cap_to_text(3)`,
+/// e.g. `cap_chown,cap_kill=ep cap_net_raw+i`. Capabilities supported by
+/// the running kernel (as reported by `runtime::last_cap`) are grouped
+/// under an empty capabilities list, meaning `all`, whenever that is
+/// shorter (e.g. `=ep cap_sys_admin-e`), as `getpcaps(8)` does.
+/// Capabilities neither known to this library nor supported by the
+/// kernel are always listed, as `cap_41`.
+/// The output can be parsed back with [`from_text`].
+///
+/// [`from_text`]: fn.from_text.html
+pub fn to_text(state: &BaseState) -> String {
+    to_text_with(state, kernel_supported())
+}
+
+/// Format a capabilities state in libcap textual format, given the set of supported capabilities.
+///
+/// This behaves like [`to_text`], but uses `supported` instead of querying
+/// the running kernel. The output can be parsed back with
+/// [`from_text_with`], given the same `supported` set.
+///
+/// [`to_text`]: fn.to_text.html
+/// [`from_text_with`]: fn.from_text_with.html
+pub fn to_text_with(state: &BaseState, supported: CapsBitSet) -> String {
+    // Flags for each capability, as a bitmask of `e`, `i`, `p`.
+    let flags_of = |index: u8| -> usize {
+        usize::from(state.effective.contains_index(index))
+            | usize::from(state.inheritable.contains_index(index)) << 1
+            | usize::from(state.permitted.contains_index(index)) << 2
+    };
+
+    // Find the most common combination of flags, preferring fewer flags.
+    let mut histo = [0usize; 8];
+    for index in supported.indices() {
+        histo[flags_of(index)] += 1;
+    }
+    let mut common = 7;
+    for t in (0..7).rev() {
+        if histo[t] >= histo[common] {
+            common = t;
+        }
+    }
+
+    // Group all capabilities not matching the common combination, including
+    // unsupported ones, as `all` covers them too when parsing. Capabilities
+    // not covered by `all` are always listed.
+    let all = CapsBitSet::all() | supported;
+    let mut groups = [CapsBitSet::new(); 8];
+    let listed = all | state.effective | state.inheritable | state.permitted;
+    for index in listed.indices() {
+        groups[flags_of(index)].insert_index(index);
+    }
+
+    let mut out = format!("={}", flags_to_text(common));
+    for t in (0..8).rev() {
+        let group = if t == common {
+            groups[t] - all
+        } else {
+            groups[t]
+        };
+        if group.is_empty() {
+            continue;
+        }
+        out.push(' ');
+        let names: Vec<String> = group.indices().map(cap_name).collect();
+        out.push_str(&names.join(","));
+        // Capabilities outside `all` with the common flags are raised explicitly.
+        let common = if t == common { 0 } else { common };
+        let raised = t & !common;
+        if raised != 0 {
+            // A leading "= " is redundant when it is followed by a raise.
+            let op = if out.starts_with("= ") {
+                out.drain(..2);
+                '='
+            } else {
+                '+'
+            };
+            out.push(op);
+            out.push_str(&flags_to_text(raised));
+        }
+        let lowered = !t & common;
+        if lowered != 0 {
+            out.push('-');
+            out.push_str(&flags_to_text(lowered));
+        }
+    }
+    out
+}
+
+/// Return the capabilities supported by the running kernel, or none if unknown.
+fn kernel_supported() -> CapsBitSet {
+    runtime::last_cap()
+        .map(CapsBitSet::up_to)
+        .unwrap_or_default()
+}
+
+/// Return the libcap name of capability `index`, e.g. `cap_chown` or `cap_41`.
+pub(crate) fn cap_name(index: u8) -> String {
+    match Capability::from_index(index) {
+        Some(c) => c.to_string().to_lowercase(),
+        None => format!("cap_{}", index),
+    }
+}
+
+/// Parse a capability name, or a `cap_<index>` or `<index>` number.
+fn cap_index(name: &str) -> Option<u8> {
+    if let Ok(c) = name.to_uppercase().parse::<Capability>() {
+        return Some(c.index());
+    }
+    let digits = match name.get(..4) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("cap_") => &name[4..],
+        _ => name,
+    };
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok().filter(|index| *index < 64)
+}
+
+fn flags_to_text(flags: usize) -> String {
+    let mut text = String::with_capacity(3);
+    for (bit, name) in &[(0, 'e'), (1, 'i'), (2, 'p')] {
+        if (flags & (1 << bit)) != 0 {
+            text.push(*name);
+        }
+    }
+    text
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    bytes: &'a [u8],
+    pos: usize,
+    /// Capabilities covered by `all` and by an empty list.
+    all: CapsBitSet,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek().map_or(false, |c| c.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn error(&self, pos: usize, msg: &str) -> CapsError {
+        CapsError::new(
+            ErrorKind::InvalidData,
+            format!("invalid capabilities text at offset {}: {}", pos, msg),
+        )
+    }
+
+    /// Parse and apply a single clause.
+    fn clause(&mut self, state: &mut BaseState) -> Result<(), CapsError> {
+        let listed = self.peek().map_or(false, is_name_char);
+        let list = if listed { self.cap_list()? } else { self.all };
+
+        let mut first = true;
+        loop {
+            let op_pos = self.pos;
+            let op = match self.peek() {
+                Some(b'=') if first => b'=',
+                Some(b'+') | Some(b'-') if first && !listed => {
+                    return Err(self.error(op_pos, "missing capabilities list"))
+                }
+                Some(c @ b'+') | Some(c @ b'-') => c,
+                Some(b'=') => return Err(self.error(op_pos, "'=' must be the first action")),
+                None if !first => break,
+                Some(c) if !first && c.is_ascii_whitespace() => break,
+                Some(_) | None => return Err(self.error(op_pos, "expected '=', '+' or '-'")),
+            };
+            self.pos += 1;
+
+            let flags = self.flags()?;
+            match op {
+                b'=' => {
+                    state.effective -= list;
+                    state.permitted -= list;
+                    state.inheritable -= list;
+                    apply(state, flags, |set| *set |= list);
+                }
+                b'+' | b'-' if flags == (false, false, false) => {
+                    return Err(self.error(self.pos, "missing flags after operator"));
+                }
+                b'+' => apply(state, flags, |set| *set |= list),
+                _ => apply(state, flags, |set| *set -= list),
+            }
+            first = false;
+        }
+        Ok(())
+    }
+
+    /// Parse a comma-separated list of capability names.
+    fn cap_list(&mut self) -> Result<CapsBitSet, CapsError> {
+        let mut list = CapsBitSet::new();
+        loop {
+            let start = self.pos;
+            while self.peek().map_or(false, is_name_char) {
+                self.pos += 1;
+            }
+            let name = &self.text[start..self.pos];
+            if name.is_empty() {
+                return Err(self.error(start, "expected capability name"));
+            }
+            if name.eq_ignore_ascii_case("all") {
+                list |= self.all;
+            } else {
+                let index = cap_index(name).ok_or_else(|| {
+                    self.error(start, &format!("unknown capability '{}'", name))
+                        .with_kind(ErrorKind::InvalidCapability)
+                })?;
+                list.insert_index(index);
+            }
+            if self.peek() != Some(b',') {
+                return Ok(list);
+            }
+            self.pos += 1;
+        }
+    }
+
+    /// Parse a list of `e`, `i`, `p` flags.
+    fn flags(&mut self) -> Result<(bool, bool, bool), CapsError> {
+        let (mut e, mut i, mut p) = (false, false, false);
+        loop {
+            match self.peek() {
+                Some(b'e') | Some(b'E') => e = true,
+                Some(b'i') | Some(b'I') => i = true,
+                Some(b'p') | Some(b'P') => p = true,
+                Some(c) if c.is_ascii_alphanumeric() || c == b'_' => {
+                    return Err(self.error(self.pos, "unknown flag, expected 'e', 'i' or 'p'"))
+                }
+                _ => return Ok((e, i, p)),
+            }
+            self.pos += 1;
+        }
+    }
+}
+
+fn is_name_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_'
+}
+
+/// Apply `action` to all the sets selected by `flags`.
+fn apply<F: Fn(&mut CapsBitSet)>(state: &mut BaseState, flags: (bool, bool, bool), action: F) {
+    let (e, i, p) = flags;
+    if e {
+        action(&mut state.effective);
+    }
+    if i {
+        action(&mut state.inheritable);
+    }
+    if p {
+        action(&mut state.permitted);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<BaseState, CapsError> {
+        from_text_with(text, CapsBitSet::all())
+    }
+
+    fn set(caps: &[Capability]) -> CapsBitSet {
+        caps.iter().collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let s = parse("cap_net_raw,cap_net_admin+eip cap_sys_nice=p").unwrap();
+        let net = set(&[Capability::CAP_NET_RAW, Capability::CAP_NET_ADMIN]);
+        assert_eq!(s.effective, net);
+        assert_eq!(s.inheritable, net);
+        assert_eq!(s.permitted, net | set(&[Capability::CAP_SYS_NICE]));
+
+        let s = parse("=ep cap_sys_admin-e").unwrap();
+        assert_eq!(s.permitted, CapsBitSet::all());
+        assert_eq!(s.effective, !set(&[Capability::CAP_SYS_ADMIN]));
+        assert!(s.inheritable.is_empty());
+
+        let s = parse("  CAP_CHOWN=eip cap_chown-i+e-p  ").unwrap();
+        assert_eq!(s.effective, set(&[Capability::CAP_CHOWN]));
+        assert!(s.inheritable.is_empty());
+        assert!(s.permitted.is_empty());
+
+        let s = parse("all=i cap_kill= ").unwrap();
+        assert_eq!(s.inheritable, !set(&[Capability::CAP_KILL]));
+
+        assert_eq!(parse("").unwrap(), BaseState::default());
+        assert_eq!(parse("=").unwrap(), BaseState::default());
+        assert_eq!(parse("=p =").unwrap(), BaseState::default());
+    }
+
+    #[test]
+    fn test_to_text() {
+        let all = CapsBitSet::all();
+        let cases = [
+            ("", "="),
+            (
+                "cap_chown,cap_kill=ep cap_net_raw+i",
+                "cap_chown,cap_kill=ep cap_net_raw+i",
+            ),
+            ("cap_net_raw=p", "cap_net_raw=p"),
+            ("=ep", "=ep"),
+            ("=ep cap_sys_resource-ep", "=ep cap_sys_resource-ep"),
+            ("all=p cap_chown=i", "=p cap_chown+i-p"),
+            ("all=eip cap_kill-p", "=eip cap_kill-p"),
+            (
+                "cap_chown=eip cap_kill=p cap_net_raw=i",
+                "cap_chown=eip cap_kill+p cap_net_raw+i",
+            ),
+        ];
+        for (input, output) in &cases {
+            let state = parse(input).unwrap();
+            assert_eq!(to_text_with(&state, all), *output, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_text_roundtrip() {
+        let all = CapsBitSet::all();
+        let partial = all - set(&[Capability::CAP_BPF, Capability::CAP_CHECKPOINT_RESTORE]);
+        let inputs = [
+            "=",
+            "=eip",
+            "cap_bpf=ep",
+            "=ep cap_checkpoint_restore-p cap_chown+i",
+            "cap_setuid,cap_bpf=eip cap_kill,cap_sys_admin+p cap_mknod=i",
+        ];
+        for input in &inputs {
+            let state = parse(input).unwrap();
+            for supported in &[all, partial] {
+                let text = to_text_with(&state, *supported);
+                assert_eq!(parse(&text).unwrap(), state, "{} -> {}", input, text);
+            }
+        }
+        let state = BaseState {
+            effective: partial,
+            permitted: partial,
+            inheritable: CapsBitSet::new(),
+        };
+        let expected = "=ep cap_bpf,cap_checkpoint_restore-ep";
+        assert_eq!(to_text_with(&state, all), expected);
+        // Unsupported capabilities are still listed, so that the output
+        // can be parsed back to the same state.
+        assert_eq!(to_text_with(&state, partial), expected);
+    }
+
+    #[test]
+    fn test_unknown_caps() {
+        let s = parse("cap_chown,cap_41=ep 42+i CAP_43=p").unwrap();
+        let mut ep = set(&[Capability::CAP_CHOWN]);
+        ep.insert_index(41);
+        assert_eq!(s.effective, ep);
+        assert_eq!(s.inheritable.indices().collect::<Vec<_>>(), vec![42]);
+        assert_eq!(
+            s.permitted.unknown().indices().collect::<Vec<_>>(),
+            vec![41, 43]
+        );
+        assert_eq!(
+            parse("cap_0=e").unwrap().effective,
+            set(&[Capability::CAP_CHOWN])
+        );
+        assert!(parse("cap_64=e").is_err());
+        assert!(parse("cap_=e").is_err());
+
+        // Capabilities supported by the kernel are covered by `all`.
+        let kernel = CapsBitSet::up_to(42);
+        let state = BaseState {
+            effective: kernel,
+            permitted: kernel,
+            inheritable: CapsBitSet::new(),
+        };
+        let text = to_text_with(&state, kernel);
+        assert_eq!(text, "=ep");
+        assert_eq!(from_text_with(&text, kernel).unwrap(), state);
+        let state = parse("cap_chown=ep cap_41+p").unwrap();
+        let text = to_text_with(&state, kernel);
+        assert_eq!(text, "cap_chown=ep cap_41+p");
+        assert_eq!(from_text_with(&text, kernel).unwrap(), state);
+
+        // Capabilities the kernel does not support are still listed.
+        let state = from_text_with("=ep", kernel).unwrap();
+        let text = to_text_with(&state, CapsBitSet::all());
+        assert_eq!(text, "=ep cap_41,cap_42+ep");
+        assert_eq!(parse(&text).unwrap(), state);
+    }
+
+    #[test]
+    fn test_from_text_errors() {
+        let cases = [
+            ("cap_foo+e", 0, "unknown capability 'cap_foo'"),
+            ("cap_chown+e cap_bar=p", 12, "unknown capability"),
+            ("+e", 0, "missing capabilities list"),
+            ("cap_chown", 9, "expected '='"),
+            ("cap_chown+", 10, "missing flags"),
+            ("cap_chown+x", 10, "unknown flag"),
+            ("cap_chown+e=p", 11, "'=' must be the first"),
+            ("cap_chown,+e", 10, "expected capability name"),
+            ("cap_chown=e,", 11, "expected '=', '+' or '-'"),
+        ];
+        for (input, pos, msg) in &cases {
+            let err = parse(input).unwrap_err().to_string();
+            assert!(
+                err.contains(&format!("offset {}:", pos)) && err.contains(msg),
+                "{}: {}",
+                input,
+                err
+            );
+        }
+    }
+}
@@ -19,7 +19,7 @@ fn test_ambient_clear() {
 fn test_ambient_drop() {
     caps::drop(None, caps::CapSet::Ambient, caps::Capability::CAP_CHOWN).unwrap();
     let no_cap = caps::has_cap(None, caps::CapSet::Ambient, caps::Capability::CAP_CHOWN).unwrap();
-    assert_eq!(no_cap, false);
+    assert!(!no_cap);
 }
 
 #[test]
@@ -52,3 +52,31 @@ fn test_ambient_set() {
     caps::drop(None, caps::CapSet::Ambient, caps::Capability::CAP_CHOWN).unwrap();
     assert!(caps::set(None, caps::CapSet::Ambient, &v).is_err());
 }
+
+#[test]
+fn test_ambient_read_bits() {
+    caps::clear(None, caps::CapSet::Ambient).unwrap();
+    let empty = caps::read_bits(None, caps::CapSet::Ambient).unwrap();
+    assert!(empty.is_empty());
+}
+
+#[test]
+fn test_ambient_own_tid() {
+    std::thread::spawn(|| {
+        let tid = unsafe { libc::syscall(libc::SYS_gettid) } as i32;
+        let cap = caps::Capability::CAP_CHOWN;
+        caps::clear(Some(tid), caps::CapSet::Ambient).unwrap();
+        caps::set(Some(tid), caps::CapSet::Ambient, &caps::CapsHashSet::new()).unwrap();
+        if caps::has_cap(None, caps::CapSet::Permitted, cap).unwrap() {
+            caps::raise(Some(tid), caps::CapSet::Inheritable, cap).unwrap();
+            caps::raise(Some(tid), caps::CapSet::Ambient, cap).unwrap();
+            assert!(caps::has_cap(Some(tid), caps::CapSet::Ambient, cap).unwrap());
+            caps::drop(Some(tid), caps::CapSet::Ambient, cap).unwrap();
+        }
+        assert!(caps::read_bits(Some(tid), caps::CapSet::Ambient)
+            .unwrap()
+            .is_empty());
+    })
+    .join()
+    .unwrap();
+}
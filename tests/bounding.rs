@@ -56,3 +56,10 @@ fn test_bounding_set() {
     let v = caps::CapsHashSet::new();
     assert!(caps::set(None, caps::CapSet::Bounding, &v).is_err());
 }
+
+#[test]
+fn test_bounding_read_bits() {
+    let bits = caps::read_bits(None, caps::CapSet::Bounding).unwrap();
+    let set = caps::read(None, caps::CapSet::Bounding).unwrap();
+    assert_eq!(caps::CapsHashSet::from(bits), set);
+}
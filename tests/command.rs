@@ -0,0 +1,73 @@
+use caps::command::{ChildCaps, CommandCapsExt};
+use caps::procfs::ProcCaps;
+use caps::securebits::SecureBits;
+use caps::{CapSet, Capability, CapsBitSet};
+use std::process::Command;
+
+fn child_status(caps: ChildCaps) -> ProcCaps {
+    let output = Command::new("/bin/cat")
+        .arg("/proc/self/status")
+        .child_caps(caps)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    ProcCaps::from_status(&String::from_utf8(output.stdout).unwrap()).unwrap()
+}
+
+#[test]
+fn test_child_ambient() {
+    if !caps::has_cap(None, CapSet::Permitted, Capability::CAP_NET_RAW).unwrap() {
+        return;
+    }
+    let net_raw = CapsBitSet::from(Capability::CAP_NET_RAW);
+    let st = child_status(ChildCaps::new().ambient(Capability::CAP_NET_RAW));
+    assert_eq!(st.ambient, net_raw);
+    assert!(st.inheritable.contains(Capability::CAP_NET_RAW));
+
+    // Without root special handling, only ambient capabilities are left.
+    let st = child_status(
+        ChildCaps::new()
+            .ambient(Capability::CAP_NET_RAW)
+            .securebits(SecureBits::NOROOT),
+    );
+    assert_eq!(st.permitted, net_raw);
+    assert_eq!(st.effective, net_raw);
+}
+
+#[test]
+fn test_child_bounding_no_new_privs() {
+    if !caps::has_cap(None, CapSet::Permitted, Capability::CAP_SETPCAP).unwrap() {
+        return;
+    }
+    let keep: CapsBitSet = [Capability::CAP_CHOWN, Capability::CAP_KILL]
+        .iter()
+        .collect();
+    let st = child_status(ChildCaps::new().bounding(keep).no_new_privs(true));
+    assert_eq!(st.bounding, keep);
+    assert_eq!(st.no_new_privs, Some(true));
+    // The parent is left untouched.
+    assert!(!caps::securebits::get_no_new_privs().unwrap());
+    assert!(caps::read_bits(None, CapSet::Bounding).unwrap().len() > 2);
+}
+
+#[test]
+fn test_child_inheritable() {
+    let st = child_status(ChildCaps::new().inheritable(CapsBitSet::new()));
+    assert!(st.inheritable.is_empty());
+    assert!(st.ambient.is_empty());
+}
+
+#[test]
+fn test_child_error() {
+    std::thread::spawn(|| {
+        caps::clear(None, CapSet::Inheritable).unwrap();
+        caps::clear(None, CapSet::Permitted).unwrap();
+        let err = Command::new("/bin/true")
+            .child_caps(ChildCaps::new().ambient(Capability::CAP_NET_RAW))
+            .status()
+            .unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+    })
+    .join()
+    .unwrap();
+}
@@ -19,7 +19,7 @@ fn test_effective_clear() {
 fn test_effective_drop() {
     caps::drop(None, caps::CapSet::Effective, caps::Capability::CAP_CHOWN).unwrap();
     let no_eff = caps::has_cap(None, caps::CapSet::Effective, caps::Capability::CAP_CHOWN).unwrap();
-    assert_eq!(no_eff, false);
+    assert!(!no_eff);
 }
 
 #[test]
@@ -44,3 +44,21 @@ fn test_effective_set() {
     caps::drop(None, caps::CapSet::Ambient, caps::Capability::CAP_CHOWN).unwrap();
     assert!(caps::set(None, caps::CapSet::Ambient, &v).is_err());
 }
+
+#[test]
+fn test_effective_read_bits() {
+    let bits = caps::read_bits(None, caps::CapSet::Effective).unwrap();
+    let set = caps::read(None, caps::CapSet::Effective).unwrap();
+    assert_eq!(caps::CapsHashSet::from(bits), set);
+}
+
+#[test]
+fn test_effective_set_bits() {
+    let perm = caps::read_bits(None, caps::CapSet::Permitted).unwrap();
+    caps::set_bits(None, caps::CapSet::Effective, caps::CapsBitSet::new()).unwrap();
+    let empty = caps::read_bits(None, caps::CapSet::Effective).unwrap();
+    assert!(empty.is_empty());
+    caps::set_bits(None, caps::CapSet::Effective, perm).unwrap();
+    let eff = caps::read_bits(None, caps::CapSet::Effective).unwrap();
+    assert_eq!(eff, perm);
+}
@@ -0,0 +1,61 @@
+use caps::errors::ErrorKind;
+use caps::securebits::{self, SecureBits};
+use caps::{CapSet, Capability};
+use std::error::Error;
+
+#[test]
+fn test_error_invalid_capability() {
+    let err = "CAP_FOO".parse::<Capability>().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidCapability);
+    assert_eq!(err.to_string(), "caps error: invalid capability: CAP_FOO");
+    assert!(err.source().is_none());
+}
+
+#[test]
+fn test_error_not_supported() {
+    let err = caps::raise(None, CapSet::Bounding, Capability::CAP_CHOWN).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotSupported);
+    assert_eq!(err.to_string(), "caps error: operation not supported");
+    assert_eq!(err.capability(), Some(Capability::CAP_CHOWN));
+    assert!(matches!(err.set(), Some(CapSet::Bounding)));
+    assert_eq!(err.tid(), Some(0));
+}
+
+#[test]
+fn test_error_errno() {
+    // No thread can have a negative ID.
+    let err = caps::read(Some(-1), CapSet::Permitted).unwrap_err();
+    assert_eq!(err.errno(), Some(libc::EINVAL));
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    assert_eq!(err.tid(), Some(-1));
+    let source = err.source().unwrap();
+    let io = source.downcast_ref::<std::io::Error>().unwrap();
+    assert_eq!(io.raw_os_error(), Some(libc::EINVAL));
+}
+
+#[test]
+fn test_error_permission_denied() {
+    std::thread::spawn(|| {
+        caps::clear(None, CapSet::Effective).unwrap();
+        let err = caps::drop(None, CapSet::Bounding, Capability::CAP_CHOWN).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        assert_eq!(err.errno(), Some(libc::EPERM));
+        assert_eq!(err.capability(), Some(Capability::CAP_CHOWN));
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+fn test_error_locked() {
+    std::thread::spawn(|| {
+        if securebits::add(SecureBits::NOROOT_LOCKED).is_err() {
+            return;
+        }
+        let err = securebits::add(SecureBits::NOROOT).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Locked);
+        assert!(err.errno().is_none());
+    })
+    .join()
+    .unwrap();
+}
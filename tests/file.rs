@@ -0,0 +1,72 @@
+use caps::errors::ErrorKind;
+use caps::file;
+use std::io::Write;
+
+#[test]
+fn test_file_read_none() {
+    let path = std::env::temp_dir().join(format!("caps-test-file-{}", std::process::id()));
+    std::fs::File::create(&path)
+        .unwrap()
+        .write_all(b"test")
+        .unwrap();
+    assert_eq!(file::read(&path).unwrap(), None);
+    let f = std::fs::File::open(&path).unwrap();
+    assert_eq!(file::read_fd(&f).unwrap(), None);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_file_read_missing() {
+    let err = file::read("/non-existent/caps-test").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    assert!(err.to_string().contains("/non-existent/caps-test"));
+}
+
+#[test]
+fn test_file_set_remove() {
+    use caps::{CapSet, Capability};
+
+    let path = std::env::temp_dir().join(format!("caps-test-set-{}", std::process::id()));
+    std::fs::File::create(&path).unwrap();
+    let mut fc = file::FileCaps::default();
+    fc.permitted.insert(Capability::CAP_NET_BIND_SERVICE);
+    fc.effective = true;
+
+    let r = file::set(&path, &fc);
+    if caps::has_cap(None, CapSet::Effective, Capability::CAP_SETFCAP).unwrap() {
+        r.unwrap();
+        assert_eq!(file::read(&path).unwrap(), Some(fc));
+        file::remove(&path).unwrap();
+        assert_eq!(file::read(&path).unwrap(), None);
+        file::remove(&path).unwrap();
+    } else {
+        let err = r.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        assert!(err.to_string().contains("CAP_SETFCAP"));
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_file_set_symlink() {
+    let link = std::env::temp_dir().join(format!("caps-test-link-{}", std::process::id()));
+    std::os::unix::fs::symlink("/bin/sh", &link).unwrap();
+    let err = file::set(&link, &file::FileCaps::default()).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::SymbolicLink);
+    assert_eq!(
+        file::remove(&link).unwrap_err().kind(),
+        ErrorKind::SymbolicLink
+    );
+    std::fs::remove_file(&link).unwrap();
+}
+
+#[test]
+fn test_file_set_no_xattr() {
+    // procfs does not support extended attributes.
+    let err = file::set("/proc/self/status", &file::FileCaps::default()).unwrap_err();
+    assert!(
+        err.kind() == ErrorKind::NotSupported || err.kind() == ErrorKind::PermissionDenied,
+        "{:?}",
+        err
+    );
+}
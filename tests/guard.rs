@@ -0,0 +1,115 @@
+use caps::{CapSet, Capability};
+
+#[test]
+fn test_elevate_drop() {
+    std::thread::spawn(|| {
+        let sys_nice = Capability::CAP_SYS_NICE;
+        if !caps::has_cap(None, CapSet::Permitted, sys_nice).unwrap() {
+            return;
+        }
+        caps::drop(None, CapSet::Effective, sys_nice).unwrap();
+        let before = caps::read_bits(None, CapSet::Effective).unwrap();
+        {
+            let guard = caps::elevate(&[sys_nice]).unwrap();
+            assert_eq!(guard.previous(), before);
+            assert!(caps::has_cap(None, CapSet::Effective, sys_nice).unwrap());
+        }
+        assert_eq!(caps::read_bits(None, CapSet::Effective).unwrap(), before);
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+fn test_elevate_restore() {
+    std::thread::spawn(|| {
+        let chown = Capability::CAP_CHOWN;
+        if !caps::has_cap(None, CapSet::Permitted, chown).unwrap() {
+            return;
+        }
+        caps::clear(None, CapSet::Effective).unwrap();
+        let guard = caps::elevate(&[chown, Capability::CAP_KILL]).unwrap();
+        assert!(caps::has_cap(None, CapSet::Effective, chown).unwrap());
+        guard.restore().unwrap();
+        assert!(caps::read_bits(None, CapSet::Effective).unwrap().is_empty());
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+fn test_elevate_panic() {
+    std::thread::spawn(|| {
+        let chown = Capability::CAP_CHOWN;
+        if !caps::has_cap(None, CapSet::Permitted, chown).unwrap() {
+            return;
+        }
+        caps::clear(None, CapSet::Effective).unwrap();
+        let r = std::panic::catch_unwind(|| {
+            let _guard = caps::elevate(&[chown]).unwrap();
+            panic!("early exit");
+        });
+        assert!(r.is_err());
+        assert!(!caps::has_cap(None, CapSet::Effective, chown).unwrap());
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+fn test_elevate_not_permitted() {
+    std::thread::spawn(|| {
+        let chown = Capability::CAP_CHOWN;
+        caps::drop(None, CapSet::Effective, chown).unwrap();
+        caps::drop(None, CapSet::Permitted, chown).unwrap();
+        let err = caps::elevate(&[chown]).unwrap_err();
+        assert_eq!(err.kind(), caps::errors::ErrorKind::PermissionDenied);
+        assert_eq!(err.capability(), Some(chown));
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+fn test_with_effective() {
+    std::thread::spawn(|| {
+        let chown = Capability::CAP_CHOWN;
+        if !caps::has_cap(None, CapSet::Permitted, chown).unwrap() {
+            return;
+        }
+        caps::drop(None, CapSet::Effective, chown).unwrap();
+        let before = caps::read_bits(None, CapSet::Effective).unwrap();
+        let out = caps::with_effective(&[chown], || {
+            caps::has_cap(None, CapSet::Effective, chown).unwrap()
+        })
+        .unwrap();
+        assert!(out.value);
+        out.restore.unwrap();
+        assert_eq!(caps::read_bits(None, CapSet::Effective).unwrap(), before);
+
+        // Panics are propagated, after restoring.
+        let r = std::panic::catch_unwind(|| {
+            caps::with_effective(&[chown], || panic!("early exit")).unwrap();
+        });
+        assert!(r.is_err());
+        assert_eq!(caps::read_bits(None, CapSet::Effective).unwrap(), before);
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+fn test_with_effective_not_permitted() {
+    std::thread::spawn(|| {
+        let (chown, kill) = (Capability::CAP_CHOWN, Capability::CAP_KILL);
+        caps::clear(None, CapSet::Effective).unwrap();
+        caps::drop(None, CapSet::Permitted, kill).unwrap();
+        let err = caps::with_effective(&[chown, kill], || unreachable!()).unwrap_err();
+        assert_eq!(err.kind(), caps::errors::ErrorKind::PermissionDenied);
+        assert_eq!(err.capability(), Some(kill));
+        // Nothing was raised.
+        assert!(caps::read_bits(None, CapSet::Effective).unwrap().is_empty());
+    })
+    .join()
+    .unwrap();
+}
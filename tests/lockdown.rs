@@ -0,0 +1,99 @@
+use caps::securebits::{self, SecureBits};
+use caps::{CapSet, Capability};
+
+#[test]
+fn test_lockdown() {
+    // Lockdown is irreversible, so run it on a throw-away thread.
+    std::thread::spawn(|| {
+        let mut keep = caps::CapsHashSet::new();
+        keep.insert(Capability::CAP_NET_BIND_SERVICE);
+        let r = caps::lockdown(&keep);
+        if !caps::has_cap(None, CapSet::Permitted, Capability::CAP_SETPCAP).unwrap() {
+            assert!(r.is_err());
+            return;
+        }
+        let report = r.unwrap();
+        assert!(!report
+            .bounding_dropped
+            .contains(Capability::CAP_NET_BIND_SERVICE));
+        assert!(report.no_new_privs_set);
+
+        let bounding = caps::read(None, CapSet::Bounding).unwrap();
+        assert!(bounding.is_subset(&keep));
+        assert!(caps::read(None, CapSet::Ambient).unwrap().is_empty());
+        let sbits = securebits::read().unwrap();
+        assert!(sbits.contains(SecureBits::NOROOT | SecureBits::NOROOT_LOCKED));
+        assert!(sbits.contains(SecureBits::NO_SETUID_FIXUP | SecureBits::NO_SETUID_FIXUP_LOCKED));
+        assert!(sbits
+            .contains(SecureBits::NO_CAP_AMBIENT_RAISE | SecureBits::NO_CAP_AMBIENT_RAISE_LOCKED));
+        assert!(securebits::remove(SecureBits::NOROOT).is_err());
+
+        // A second lockdown has nothing left to change.
+        let again = caps::lockdown(&keep).unwrap();
+        assert_eq!(again, caps::LockdownReport::default());
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+fn test_restrict_to() {
+    std::thread::spawn(|| {
+        for c in &[Capability::CAP_NET_RAW, Capability::CAP_KILL] {
+            caps::raise(None, CapSet::Inheritable, *c).unwrap();
+            caps::raise(None, CapSet::Ambient, *c).unwrap();
+        }
+        let orig = caps::CapState::current().unwrap();
+        let keep: caps::CapsHashSet = [Capability::CAP_NET_RAW, Capability::CAP_CHOWN]
+            .iter()
+            .copied()
+            .collect();
+        let keep_bits = caps::CapsBitSet::from(&keep);
+        let state = caps::restrict_to(&keep).unwrap();
+        assert_eq!(state, caps::CapState::current().unwrap());
+        for cset in &[
+            CapSet::Effective,
+            CapSet::Permitted,
+            CapSet::Inheritable,
+            CapSet::Ambient,
+            CapSet::Bounding,
+        ] {
+            assert_eq!(state.get(*cset), orig.get(*cset) & keep_bits, "{:?}", cset);
+        }
+        assert!(!state.effective.contains(Capability::CAP_SETPCAP));
+        assert_eq!(
+            state.ambient,
+            caps::CapsBitSet::from(Capability::CAP_NET_RAW)
+        );
+
+        // Restricting again is a no-op, even without CAP_SETPCAP.
+        assert_eq!(caps::restrict_to(&keep).unwrap(), state);
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+fn test_restrict_to_no_ambient_raise() {
+    if caps::runtime::ambient_set_supported().is_err() {
+        return;
+    }
+    std::thread::spawn(|| {
+        for c in &[Capability::CAP_NET_RAW, Capability::CAP_KILL] {
+            caps::raise(None, CapSet::Inheritable, *c).unwrap();
+            caps::raise(None, CapSet::Ambient, *c).unwrap();
+        }
+        caps::securebits::add(caps::securebits::SecureBits::NO_CAP_AMBIENT_RAISE).unwrap();
+        let keep: caps::CapsHashSet = [Capability::CAP_NET_RAW, Capability::CAP_SETPCAP]
+            .iter()
+            .copied()
+            .collect();
+        let state = caps::restrict_to(&keep).unwrap();
+        assert_eq!(
+            state.ambient,
+            caps::CapsBitSet::from(Capability::CAP_NET_RAW)
+        );
+    })
+    .join()
+    .unwrap();
+}
@@ -0,0 +1,67 @@
+use caps::procfs;
+use caps::{CapSet, CapState, Capability};
+
+#[test]
+fn test_procfs_read_tid() {
+    let pid = unsafe { libc::getpid() };
+    let tid = unsafe { libc::syscall(libc::SYS_gettid) } as i32;
+    let st = procfs::read_tid(pid, tid, None).unwrap();
+    let current = CapState::current().unwrap();
+    assert_eq!(st.base(), current.base());
+    assert_eq!(st.ambient, current.ambient);
+    assert_eq!(st.bounding, current.bounding);
+    assert_eq!(st.no_new_privs, Some(current.no_new_privs));
+}
+
+#[test]
+fn test_procfs_read_pid() {
+    let pid = unsafe { libc::getpid() };
+    let p1 = procfs::read_pid(pid, None).unwrap();
+    let p2 = procfs::read_pid(pid, Some(std::path::PathBuf::from("/proc"))).unwrap();
+    assert_eq!(p1, p2);
+    assert!(procfs::read_pid(-1, None).is_err());
+}
+
+#[test]
+fn test_procfs_other_thread() {
+    let pid = unsafe { libc::getpid() };
+    let (tx, rx) = std::sync::mpsc::channel();
+    let (done_tx, done_rx) = std::sync::mpsc::channel::<()>();
+    let t = std::thread::spawn(move || {
+        let tid = unsafe { libc::syscall(libc::SYS_gettid) } as i32;
+        caps::drop(None, CapSet::Effective, Capability::CAP_CHOWN).unwrap();
+        tx.send(tid).unwrap();
+        done_rx.recv().unwrap();
+    });
+    let tid = rx.recv().unwrap();
+    let st = procfs::read_tid(pid, tid, None).unwrap();
+    assert!(!st.effective.contains(Capability::CAP_CHOWN));
+    done_tx.send(()).unwrap();
+    t.join().unwrap();
+}
+
+#[test]
+fn test_read_other_thread_ambient_bounding() {
+    let (tx, rx) = std::sync::mpsc::channel();
+    let (done_tx, done_rx) = std::sync::mpsc::channel::<()>();
+    let t = std::thread::spawn(move || {
+        let tid = unsafe { libc::syscall(libc::SYS_gettid) } as i32;
+        let _ = caps::drop(None, CapSet::Bounding, Capability::CAP_MKNOD);
+        let bounding = caps::read(None, CapSet::Bounding).unwrap();
+        let ambient = caps::read(None, CapSet::Ambient).unwrap();
+        // The calling thread itself is handled without procfs.
+        assert_eq!(caps::read(Some(tid), CapSet::Bounding).unwrap(), bounding);
+        tx.send((tid, bounding, ambient)).unwrap();
+        done_rx.recv().unwrap();
+    });
+    let (tid, bounding, ambient) = rx.recv().unwrap();
+    assert_eq!(caps::read(Some(tid), CapSet::Bounding).unwrap(), bounding);
+    assert_eq!(caps::read(Some(tid), CapSet::Ambient).unwrap(), ambient);
+    assert_eq!(
+        caps::read_bits(Some(tid), CapSet::Bounding).unwrap(),
+        caps::CapsBitSet::from(&bounding)
+    );
+    assert!(!caps::has_cap(Some(tid), CapSet::Bounding, Capability::CAP_MKNOD).unwrap());
+    done_tx.send(()).unwrap();
+    t.join().unwrap();
+}
@@ -0,0 +1,96 @@
+use caps::raw;
+use caps::{CapSet, Capability};
+
+#[test]
+fn test_raw_capget() {
+    let version = raw::capget_version();
+    let masks = raw::capget(version, 0).unwrap();
+    let base = caps::read_base(None).unwrap();
+    assert_eq!(masks.effective, base.effective.to_mask());
+    assert_eq!(masks.permitted, base.permitted.to_mask());
+    assert_eq!(masks.inheritable, base.inheritable.to_mask());
+
+    assert_eq!(raw::capget(version, -1), Err(libc::EINVAL));
+}
+
+#[test]
+fn test_raw_capset() {
+    std::thread::spawn(|| {
+        let version = raw::capget_version();
+        let mut masks = raw::capget(version, 0).unwrap();
+        masks.effective = 0;
+        raw::capset(version, 0, &masks).unwrap();
+        assert!(caps::read(None, CapSet::Effective).unwrap().is_empty());
+
+        // Effective must be a subset of Permitted.
+        masks.effective = !0;
+        masks.permitted = 0;
+        assert_eq!(raw::capset(version, 0, &masks), Err(libc::EPERM));
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+fn test_raw_bounding() {
+    let last_cap = caps::runtime::last_cap().unwrap();
+    let mask = raw::bounding_read(last_cap).unwrap();
+    assert_eq!(
+        mask,
+        caps::read_bits(None, CapSet::Bounding).unwrap().to_mask()
+    );
+    assert_eq!(raw::bounding_has(63), Err(libc::EINVAL));
+
+    std::thread::spawn(move || {
+        if !caps::has_cap(None, CapSet::Effective, Capability::CAP_SETPCAP).unwrap() {
+            return;
+        }
+        let keep = Capability::CAP_SETPCAP.bitmask() | Capability::CAP_CHOWN.bitmask();
+        raw::bounding_restrict(keep, last_cap).unwrap();
+        assert_eq!(raw::bounding_read(last_cap).unwrap(), keep & mask);
+        assert!(!raw::bounding_has(Capability::CAP_KILL.index()).unwrap());
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+fn test_raw_ambient() {
+    if caps::runtime::ambient_set_supported().is_err() {
+        return;
+    }
+    std::thread::spawn(|| {
+        let last_cap = caps::runtime::last_cap().unwrap();
+        let chown = Capability::CAP_CHOWN.bitmask();
+        raw::ambient_clear().unwrap();
+        assert_eq!(raw::ambient_read(last_cap).unwrap(), 0);
+        if !caps::has_cap(None, CapSet::Permitted, Capability::CAP_CHOWN).unwrap() {
+            return;
+        }
+        caps::raise(None, CapSet::Inheritable, Capability::CAP_CHOWN).unwrap();
+        raw::ambient_write(chown, last_cap).unwrap();
+        assert_eq!(raw::ambient_read(last_cap).unwrap(), chown);
+        raw::ambient_lower(Capability::CAP_CHOWN.index()).unwrap();
+        assert!(!raw::ambient_has(Capability::CAP_CHOWN.index()).unwrap());
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+fn test_raw_flags() {
+    std::thread::spawn(|| {
+        let bits = raw::get_securebits().unwrap();
+        assert_eq!(bits, caps::securebits::read().unwrap().bits());
+        raw::set_keepcaps(true).unwrap();
+        assert!(raw::get_keepcaps().unwrap());
+        raw::set_keepcaps(false).unwrap();
+        assert!(!raw::get_keepcaps().unwrap());
+        assert_eq!(
+            raw::get_no_new_privs().unwrap(),
+            caps::securebits::get_no_new_privs().unwrap()
+        );
+    })
+    .join()
+    .unwrap();
+}
@@ -7,7 +7,7 @@ fn test_ambient_supported() {
 
 #[test]
 fn test_thread_all_supported() {
-    assert!(runtime::thread_all_supported().len() > 0);
+    assert!(!runtime::thread_all_supported().is_empty());
     assert!(runtime::thread_all_supported().len() <= caps::all().len());
 }
 
@@ -20,7 +20,7 @@ fn test_procfs_all_supported() {
     let thread = runtime::thread_all_supported();
     let all = caps::all();
 
-    assert!(thread.len() > 0);
+    assert!(!thread.is_empty());
     assert!(thread.len() <= all.len());
     assert_eq!(
         p1,
@@ -35,3 +35,39 @@ fn test_procfs_all_supported() {
         p1.symmetric_difference(&thread).collect::<Vec<_>>()
     );
 }
+
+#[test]
+fn test_procfs_last_cap() {
+    let last_cap = runtime::procfs_last_cap(None).unwrap();
+    let supported = caps::CapsBitSet::up_to(last_cap);
+    let bounding = caps::read_bits(None, caps::CapSet::Bounding).unwrap();
+    assert!(bounding.is_subset(&supported));
+    assert_eq!(
+        caps::CapsBitSet::from(runtime::procfs_all_supported(None).unwrap()),
+        supported & caps::CapsBitSet::all()
+    );
+}
+
+#[test]
+fn test_last_cap() {
+    let last_cap = runtime::last_cap().unwrap();
+    assert_eq!(last_cap, runtime::procfs_last_cap(None).unwrap());
+    // Cached value is returned on later calls.
+    assert_eq!(runtime::last_cap().unwrap(), last_cap);
+}
+
+#[test]
+fn test_features() {
+    let features = runtime::features().unwrap();
+    assert!(features.ambient);
+    assert!(features.securebits);
+    assert_eq!(features.capget_version, 0x2008_0522);
+    assert_eq!(features.last_cap, runtime::procfs_last_cap(None).unwrap());
+    assert!(features.supported().is_superset(&caps::CapsBitSet::from(
+        runtime::procfs_all_supported(None).unwrap()
+    )));
+
+    // Detection runs again after a reset, with the same outcome.
+    runtime::reset_features();
+    assert_eq!(runtime::features().unwrap(), features);
+}
@@ -11,3 +11,79 @@ fn test_keepcaps() {
     let f2 = securebits::has_keepcaps().unwrap();
     assert_eq!(f0, f2);
 }
+
+#[test]
+fn test_securebits_flags() {
+    use securebits::SecureBits;
+
+    let mut bits = SecureBits::NOROOT | SecureBits::KEEP_CAPS;
+    assert!(bits.contains(SecureBits::NOROOT));
+    assert!(!bits.contains(SecureBits::NOROOT_LOCKED));
+    assert_eq!(
+        bits.locks(),
+        SecureBits::NOROOT_LOCKED | SecureBits::KEEP_CAPS_LOCKED
+    );
+    assert_eq!(bits.to_string(), "SECURE_NOROOT|SECURE_KEEP_CAPS");
+    bits.remove(SecureBits::KEEP_CAPS);
+    bits.insert(SecureBits::NOROOT_LOCKED);
+    assert_eq!(bits.to_string(), "SECURE_NOROOT|SECURE_NOROOT_LOCKED");
+    assert_eq!(SecureBits::from_bits(bits.bits()), Some(bits));
+    assert_eq!(SecureBits::from_bits(0x100), None);
+    assert!(SecureBits::empty().is_empty());
+
+    let locked = SecureBits::NOROOT | SecureBits::NOROOT_LOCKED;
+    let err = SecureBits::NOROOT_LOCKED
+        .check_transition(locked)
+        .unwrap_err();
+    assert!(err.to_string().contains("SECURE_NOROOT is locked"));
+    let err = SecureBits::NOROOT.check_transition(locked).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("SECURE_NOROOT_LOCKED cannot be cleared"));
+    (locked | SecureBits::KEEP_CAPS)
+        .check_transition(locked)
+        .unwrap();
+}
+
+#[test]
+fn test_securebits_roundtrip() {
+    use caps::{CapSet, Capability};
+    use securebits::SecureBits;
+
+    let orig = securebits::read().unwrap();
+    let r = securebits::add(SecureBits::KEEP_CAPS);
+    if !caps::has_cap(None, CapSet::Effective, Capability::CAP_SETPCAP).unwrap() {
+        assert!(r.is_err());
+        return;
+    }
+    r.unwrap();
+    assert!(securebits::has_keepcaps().unwrap());
+    assert!(securebits::read().unwrap().contains(SecureBits::KEEP_CAPS));
+    securebits::remove(SecureBits::KEEP_CAPS).unwrap();
+    assert!(!securebits::has_keepcaps().unwrap());
+
+    // Locking is per-thread, so do it on a throw-away thread.
+    std::thread::spawn(|| {
+        securebits::add(SecureBits::NO_CAP_AMBIENT_RAISE | SecureBits::NO_CAP_AMBIENT_RAISE_LOCKED)
+            .unwrap();
+        let err = securebits::remove(SecureBits::NO_CAP_AMBIENT_RAISE).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("SECURE_NO_CAP_AMBIENT_RAISE is locked"));
+    })
+    .join()
+    .unwrap();
+    assert_eq!(securebits::read().unwrap(), orig);
+}
+
+#[test]
+fn test_no_new_privs() {
+    // The flag cannot be cleared, so set it on a throw-away thread.
+    std::thread::spawn(|| {
+        securebits::set_no_new_privs().unwrap();
+        assert!(securebits::get_no_new_privs().unwrap());
+        assert!(caps::CapState::current().unwrap().no_new_privs);
+    })
+    .join()
+    .unwrap();
+}
@@ -0,0 +1,67 @@
+use caps::{CapSet, CapState};
+
+#[test]
+fn test_state_current() {
+    let state = CapState::current().unwrap();
+    state.validate().unwrap();
+    for cset in &[
+        CapSet::Effective,
+        CapSet::Permitted,
+        CapSet::Inheritable,
+        CapSet::Ambient,
+        CapSet::Bounding,
+    ] {
+        let bits = caps::read_bits(None, *cset).unwrap();
+        assert_eq!(state.get(*cset), bits);
+    }
+    assert_eq!(state, CapState::current().unwrap());
+}
+
+#[test]
+#[cfg(feature = "serde_support")]
+fn test_state_serde() {
+    let state = CapState::current().unwrap();
+    let ser = serde_json::to_string(&state).unwrap();
+    let deser: CapState = serde_json::from_str(&ser).unwrap();
+    assert_eq!(deser, state);
+}
+
+#[test]
+fn test_set_base() {
+    let state = CapState::current().unwrap();
+    let orig = state.base();
+
+    // Clear effective and set inheritable to permitted in a single call.
+    let mut next = orig;
+    next.effective = caps::CapsBitSet::new();
+    next.inheritable = orig.permitted;
+    let r = caps::set_base(None, &next);
+    if orig
+        .permitted
+        .is_subset(&(orig.inheritable | state.bounding))
+    {
+        r.unwrap();
+        assert_eq!(CapState::current().unwrap().base(), next);
+    }
+
+    // Raising effective beyond permitted is rejected, with details.
+    let mut bad = next;
+    bad.permitted = caps::CapsBitSet::new();
+    bad.effective = caps::CapsBitSet::from(caps::Capability::CAP_CHOWN);
+    let err = caps::set_base(None, &bad).unwrap_err();
+    assert!(err.to_string().contains("effective set is not a subset"));
+}
+
+#[test]
+fn test_read_base() {
+    let base = caps::read_base(None).unwrap();
+    assert_eq!(base, CapState::current().unwrap().base());
+
+    let mut next = base;
+    next.drop(CapSet::Effective, caps::Capability::CAP_CHOWN)
+        .unwrap();
+    caps::set_base(None, &next).unwrap();
+    assert!(!caps::has_cap(None, CapSet::Effective, caps::Capability::CAP_CHOWN).unwrap());
+    caps::set_base(None, &base).unwrap();
+    assert_eq!(caps::read_base(None).unwrap(), base);
+}